@@ -0,0 +1,58 @@
+use reqwest::StatusCode;
+
+/// Errors that can occur while talking to the eTilbudsavis API.
+#[derive(Debug)]
+pub enum Error {
+    /// The API answered with a non-success status code.
+    Status { url: String, status: StatusCode },
+    /// The request could not be sent or the response could not be read.
+    Network(reqwest::Error),
+    /// The API answered with a body that could not be decoded.
+    Decode { url: String, source: reqwest::Error },
+    /// Retrieving the offers of a single catalog failed.
+    Catalog {
+        catalog_id: String,
+        source: Box<Error>,
+    },
+    /// A spawned request task panicked or was cancelled.
+    Task(tokio::task::JoinError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Status { url, status } => write!(f, "API returned {status} for {url}"),
+            Error::Network(_) => write!(f, "Could not reach the API"),
+            Error::Decode { url, .. } => write!(f, "API returned invalid JSON for {url}"),
+            Error::Catalog { catalog_id, .. } => {
+                write!(f, "Failed to retrieve offers from catalog {catalog_id}")
+            }
+            Error::Task(_) => write!(f, "Request task did not complete"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Status { .. } => None,
+            Error::Network(err) | Error::Decode { source: err, .. } => Some(err),
+            Error::Catalog { source, .. } => Some(source.as_ref()),
+            Error::Task(err) => Some(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Network(err)
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Error::Task(err)
+    }
+}
@@ -0,0 +1,13 @@
+//! Library for interacting with the eTilbudsavis API.
+//!
+//! The `etb` binary is a thin consumer of this crate, so everything it can do
+//! is available to other Rust programs as well.
+
+pub mod error;
+pub mod mock;
+pub mod output;
+pub mod requests;
+
+pub use error::Error;
+pub use output::OutputFormat;
+pub use requests::{client::Client, dealer::Dealer, offer::Offer, userdata::UserData};
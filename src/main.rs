@@ -1,13 +1,28 @@
-mod output;
-mod requests;
-
-use crate::requests::{dealer::Dealer, offer::Offer, userdata::UserData};
+use anyhow::Context;
+use chrono::NaiveDate;
 use clap::{Command, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{Generator, Shell, generate};
 use clap_complete_nushell::Nushell;
-use output::OutputFormat;
-use requests::offer::sort_by_cost;
-use std::process::exit;
+use etilbudsavis::{
+    Client, OutputFormat, UserData,
+    mock::{Fixtures, MockServer},
+    output,
+    requests::{
+        category::Category,
+        client::{ClientOptions, DEFAULT_API_URL},
+        filter::{Comparison, OfferFilter},
+        location::Location,
+        offer::{OfferComparator, SortKey},
+        query::parse_query,
+        unit::UnitPrice,
+        userdata::RetrievalIssues,
+    },
+};
+use std::{
+    path::PathBuf,
+    process::{ExitCode, exit},
+    time::Duration,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 enum Shells {
@@ -17,39 +32,180 @@ enum Shells {
     Zsh,
 }
 
+/// Parse a percentage such as `30%` or `30`.
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    let percentage = value
+        .trim()
+        .trim_end_matches('%')
+        .trim_end()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid percentage: {value}"))?;
+    if !(0.0..=100.0).contains(&percentage) {
+        return Err(format!("percentage must be between 0 and 100: {value}"));
+    }
+    Ok(percentage)
+}
+
+/// Parse a radius in kilometers, e.g. `2.5`, into meters.
+fn parse_radius(value: &str) -> Result<u32, String> {
+    let kilometers = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid radius: {value}"))?;
+    let meters = (kilometers * 1000.0).round();
+    // Also rejects `NaN` and `inf`, which parse as numbers.
+    if !(1.0..=u32::MAX as f64).contains(&meters) {
+        return Err(format!(
+            "radius must be a positive number of kilometers: {value}"
+        ));
+    }
+    Ok(meters as u32)
+}
+
 #[derive(Parser, Debug)]
 #[command(
     author, version, about = "A CLI interface for the eTilbudsavis API.", long_about = None
 )]
 struct Cli {
+    /// Search terms and fields, e.g. `kaffe dealer:netto price<40 unit:kg`.
     search: Vec<String>,
 
     /// The desired output format.
     #[arg(short, long)]
     format: Option<OutputFormat>,
 
-    /// Search by dealer.
+    /// Only show offers from these comma-separated dealers.
+    #[arg(short, long, value_delimiter = ',')]
+    dealer: Vec<String>,
+
+    /// Treat the search terms as dealers and show all of their offers.
+    #[arg(short = 'D', long, conflicts_with_all = ["dealer", "remote"])]
+    by_dealer: bool,
+
+    /// Leave out offers whose name contains this, ignoring case and accents.
+    #[arg(short, long)]
+    exclude: Vec<String>,
+
+    /// Only show offers costing at most this much.
+    #[arg(long)]
+    max_price: Option<f64>,
+
+    /// Only show offers costing at most this much per unit, e.g. `50/kg`.
+    #[arg(long)]
+    max_unit_price: Option<UnitPrice>,
+
+    /// Only show offers valid on this date, e.g. `2026-10-20`.
+    #[arg(long)]
+    valid_on: Option<NaiveDate>,
+
+    /// Only show offers in one of these comma-separated categories, see `categories`.
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    category: Vec<Category>,
+
+    /// Search the offers of every dealer through the API, not just favorites.
+    #[arg(short, long)]
+    remote: bool,
+
+    /// How to order the offers, by several comma-separated keys in turn.
+    /// Defaults to most expensive per unit first.
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    sort: Vec<SortKey>,
+
+    /// Reverse the order of the offers.
+    #[arg(long)]
+    reverse: bool,
+
+    /// Only show offers discounted by at least this much, e.g. `30%`.
+    #[arg(long, value_parser = parse_percentage)]
+    min_discount: Option<f64>,
+
+    /// Add a column of descriptions and catalog pages to tables.
+    #[arg(long)]
+    details: bool,
+
+    /// Only show offers near this postcode or `latitude,longitude` for this search.
+    #[arg(short, long)]
+    near: Option<Location>,
+
+    /// Search radius around the location, in kilometers.
+    #[arg(long, value_parser = parse_radius)]
+    radius: Option<u32>,
+
+    /// Report details such as how many duplicate offers were collapsed.
     #[arg(short, long)]
-    dealer: bool,
+    verbose: bool,
+
+    /// Base URL of the API, e.g. a local `mock-server`.
+    #[arg(long, env = "ETB_API_URL", default_value = DEFAULT_API_URL)]
+    api_url: String,
+
+    /// Seconds before a single API request times out.
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// How many times a failed API request is retried.
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Maximum number of API requests in flight at once.
+    #[arg(long, default_value_t = 8)]
+    max_requests: usize,
+
+    /// Maximum number of catalogs or offers retrieved per request, across all pages.
+    #[arg(long, default_value_t = 1000)]
+    max_results: usize,
 
     #[arg(long = "generate", value_enum)]
     generator: Option<Shells>,
 
     #[command(subcommand)]
-    favorites: Option<FavoriteCommands>,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 #[command(author, version, about, long_about = None)]
-enum FavoriteCommands {
-    #[command(about = "Add a dealer to favorites")]
-    Add { dealers: Vec<Dealer> },
-    #[command(about = "Remove a dealer from favorites")]
-    Remove { dealers: Vec<Dealer> },
+enum Commands {
+    #[command(about = "Add a dealer to favorites, by name or id")]
+    Add { dealers: Vec<String> },
+    #[command(about = "Remove a dealer from favorites, by name or id")]
+    Remove { dealers: Vec<String> },
     #[command(about = "List available dealers")]
     Dealers,
     #[command(about = "List currently set favorites")]
     Favorites,
+    #[command(about = "List categories of the offers of favorites, with their number of offers")]
+    Categories,
+    #[command(about = "List stores near your location, for favorites or the given dealers")]
+    Stores { dealers: Vec<String> },
+    #[command(about = "Show or set the location offers are retrieved for")]
+    Location {
+        /// Postcode or `latitude,longitude`.
+        place: Option<Location>,
+        /// Forget the saved location and retrieve national offers.
+        #[arg(long, conflicts_with = "place")]
+        clear: bool,
+    },
+    #[command(about = "Inspect the cached offers of favorites")]
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    #[command(about = "Serve recorded API responses for offline testing")]
+    MockServer {
+        /// Port to listen on.
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+        /// Directory of recorded responses, e.g. `<dir>/v2/catalogs.json`.
+        /// Defaults to the recordings bundled with etb.
+        #[arg(long)]
+        fixtures: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    #[command(about = "Show how old the cached offers of each favorite are")]
+    Status,
 }
 
 fn print_completions<G: Generator>(generator: G, cmd: &mut Command) {
@@ -61,12 +217,69 @@ fn print_completions<G: Generator>(generator: G, cmd: &mut Command) {
     );
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Cli::parse();
+/// Warn that `truncated` listings stopped at `--max-results` and may be missing offers.
+fn warn_truncated(truncated: &[String], max_results: usize) {
+    for listing in truncated {
+        eprintln!(
+            "Only the first {max_results} results of {listing} were retrieved, raise --max-results to get the rest"
+        );
+    }
+}
 
-    let mut userdata = UserData::from_cache().unwrap_or_default();
+/// Combine the search terms and filter flags into one filter.
+async fn offer_filter(
+    args: &Cli,
+    userdata: &mut UserData,
+    client: &Client,
+) -> anyhow::Result<OfferFilter> {
+    let mut filter = if args.by_dealer {
+        let dealers = userdata
+            .resolve_dealers(client, &args.search)
+            .await
+            .context("Search term did not match any known dealers")?;
+        if dealers.is_empty() {
+            OfferFilter::All
+        } else {
+            OfferFilter::Dealers(dealers)
+        }
+    } else if !args.dealer.is_empty() {
+        let dealers = userdata.resolve_dealers(client, &args.dealer).await?;
+        parse_query(&args.search)?.and(OfferFilter::Dealers(dealers))
+    } else {
+        parse_query(&args.search)?
+    };
 
+    for exclude in &args.exclude {
+        filter = filter.and(OfferFilter::NameContains(exclude.clone()).negate());
+    }
+    if let Some(max_price) = args.max_price {
+        filter = filter.and(OfferFilter::Price(Comparison::AtMost, max_price));
+    }
+    if let Some(max_unit_price) = args.max_unit_price {
+        filter = filter.and(OfferFilter::UnitPrice(Comparison::AtMost, max_unit_price));
+    }
+    if let Some(min_discount) = args.min_discount {
+        filter = filter.and(OfferFilter::Discount(Comparison::AtLeast, min_discount));
+    }
+    if let Some(valid_on) = args.valid_on {
+        filter = filter.and(OfferFilter::ValidOn(valid_on));
+    }
+    if let Some(categories) = args
+        .category
+        .iter()
+        .map(|category| OfferFilter::Category(*category))
+        .reduce(OfferFilter::or)
+    {
+        filter = filter.and(categories);
+    }
+    Ok(filter)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    let mut args = Cli::parse();
+
+    // Completions need no user data, so they must not wait for another running etb.
     if let Some(shell) = args.generator {
         let mut cmd = Cli::command();
         eprintln!("Generating completion file for {shell:?}...");
@@ -83,25 +296,172 @@ async fn main() {
         exit(0);
     }
 
-    match args.favorites {
-        Some(FavoriteCommands::Add { dealers }) => userdata.add_favorites(&dealers),
-        Some(FavoriteCommands::Remove { dealers }) => userdata.remove_favorites(&dealers),
-        Some(FavoriteCommands::Dealers) => {
-            Dealer::list_known_dealers(args.format);
-            exit(0);
+    let mut userdata = UserData::load().await?;
+    for warning in userdata.load_warnings() {
+        eprintln!("Warning: {warning}");
+    }
+    let client = Client::from_options(ClientOptions {
+        base_url: args.api_url.clone(),
+        timeout: Duration::from_secs(args.timeout),
+        max_retries: args.retries,
+        max_concurrent_requests: args.max_requests,
+        max_results: args.max_results,
+        ..ClientOptions::default()
+    })?;
+
+    let with_radius = |mut location: Location| {
+        if let Some(radius) = args.radius {
+            location.radius = radius;
+        }
+        location
+    };
+    if let Some(near) = args.near {
+        userdata.override_location(with_radius(near));
+    } else if args.radius.is_some()
+        && let Some(location) = userdata.location()
+    {
+        userdata.override_location(with_radius(location));
+    }
+
+    let format = args.format.or(userdata.config().format);
+    let list_format = format.unwrap_or(OutputFormat::Table);
+    match args.command.take() {
+        Some(Commands::Add { dealers }) => {
+            let dealers = userdata.resolve_dealers(&client, &dealers).await?;
+            userdata.add_favorites(&dealers)?;
+            return Ok(ExitCode::SUCCESS);
         }
-        Some(FavoriteCommands::Favorites) => {
-            userdata.print_favorites(args.format);
-            exit(0);
+        Some(Commands::Remove { dealers }) => {
+            let dealers = userdata.resolve_dealers(&client, &dealers).await?;
+            userdata.remove_favorites(&dealers)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Dealers) => {
+            let dealers = userdata.available_dealers(&client).await?;
+            println!(
+                "{}",
+                output::format_dealers(dealers, "Dealers", list_format)?
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Favorites) => {
+            let favorites = userdata.favorites();
+            println!(
+                "{}",
+                output::format_dealers(&favorites, "Favorites", list_format)?
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Categories) => {
+            let (categories, issues) = userdata.categories(&client).await?;
+            warn_truncated(&issues.truncated, args.max_results);
+            for failure in issues.failures {
+                eprintln!(
+                    "Failed to retrieve offers from {}: {:#}",
+                    failure.dealer,
+                    anyhow::Error::new(failure.error)
+                );
+            }
+            println!("{}", output::format_categories(&categories, list_format)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Stores { dealers }) => {
+            let stores = userdata.stores(&client, &dealers).await?;
+            println!("{}", output::format_stores(&stores, list_format)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Location {
+            place: None,
+            clear: false,
+        }) => {
+            match userdata.location() {
+                Some(location) => println!("{location}"),
+                None => println!("No location set, retrieving national offers"),
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Location { place, .. }) => {
+            userdata.set_location(place.map(with_radius))?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::Cache {
+            command: CacheCommand::Status,
+        }) => {
+            let status = userdata.cache_status();
+            println!("{}", output::format_cache_status(&status, list_format)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Commands::MockServer { port, fixtures }) => {
+            // Let other etb processes use the user data while serving.
+            drop(userdata);
+            let fixtures = fixtures.map(Fixtures::Directory).unwrap_or_default();
+            let server = MockServer::bind(("127.0.0.1", port), fixtures).await?;
+            eprintln!("Serving recorded API responses on {}", server.url()?);
+            server.run().await?;
+            return Ok(ExitCode::SUCCESS);
         }
         None => (),
     };
 
-    let mut offers = userdata.search(&args.search, args.dealer).await;
-    offers.sort_unstable_by(|a, b| sort_by_cost(a, b));
+    let filter = offer_filter(&args, &mut userdata, &client).await?;
+
+    // Without a sort order, the cheapest per unit end up at the bottom, next to the prompt.
+    let config = userdata.config();
+    let (keys, reverse) = if !args.sort.is_empty() {
+        (args.sort, args.reverse)
+    } else if !config.sort.is_empty() {
+        (config.sort.clone(), config.reverse != args.reverse)
+    } else {
+        (vec![SortKey::UnitPrice], !args.reverse)
+    };
+
+    let remote_offers;
+    let (mut offers, issues) = if args.remote {
+        let retrieved = userdata.search_remote(&client, &filter).await?;
+        remote_offers = retrieved.offers;
+        let issues = RetrievalIssues {
+            failures: Vec::new(),
+            malformed: retrieved.malformed,
+            duplicates: retrieved.duplicates,
+            truncated: retrieved.truncated,
+        };
+        (remote_offers.iter().collect::<Vec<_>>(), issues)
+    } else {
+        let results = userdata.search(&client, &filter).await?;
+        (results.offers, results.issues)
+    };
 
-    match args.format {
-        Some(format) => output::print_offers(offers, &format),
+    if args.verbose && issues.duplicates > 0 {
+        eprintln!("Collapsed {} duplicate offers", issues.duplicates);
+    }
+    for malformed in &issues.malformed {
+        eprintln!("Skipped malformed {malformed}");
+    }
+    warn_truncated(&issues.truncated, args.max_results);
+    let any_dealer_failed = !issues.failures.is_empty();
+    for failure in issues.failures {
+        eprintln!(
+            "Failed to retrieve offers from {}: {:#}",
+            failure.dealer,
+            anyhow::Error::new(failure.error)
+        );
+    }
+
+    let comparator = keys
+        .into_iter()
+        .fold(OfferComparator::new(), OfferComparator::then_by)
+        .reversed(reverse)
+        .relevance_to(filter.clone());
+    offers.sort_by(|a, b| comparator.compare(a, b));
+
+    match format {
+        Some(format) => println!("{}", output::format_offers(&offers, format, args.details)?),
         None => println!("Amount of offers: {}", offers.len()),
     }
+
+    if any_dealer_failed {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
 }
@@ -0,0 +1,361 @@
+//! A small HTTP server replaying recorded API responses.
+//!
+//! Pointing a [`Client`](crate::Client) at the server lets the whole fetch → deserialize →
+//! cache → search pipeline run without network access.
+
+use crate::requests::location::Location;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+/// Recordings bundled with the crate, keyed by request path.
+const BUNDLED: &[(&str, &str)] = &[
+    ("/v2/dealers", include_str!("fixtures/v2/dealers.json")),
+    ("/v2/catalogs", include_str!("fixtures/v2/catalogs.json")),
+    ("/v2/stores", include_str!("fixtures/v2/stores.json")),
+    (
+        "/v2/offers/search",
+        include_str!("fixtures/v2/offers/search.json"),
+    ),
+    (
+        "/v2/catalogs/8b1e2Qx1/hotspots",
+        include_str!("fixtures/v2/catalogs/8b1e2Qx1/hotspots.json"),
+    ),
+    (
+        "/v2/catalogs/8b1e9Rg2/hotspots",
+        include_str!("fixtures/v2/catalogs/8b1e9Rg2/hotspots.json"),
+    ),
+    (
+        "/v2/catalogs/c4f7aLm9/hotspots",
+        include_str!("fixtures/v2/catalogs/c4f7aLm9/hotspots.json"),
+    ),
+];
+
+/// Largest request head the server is willing to read.
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+/// Where the mock server reads its responses from.
+#[derive(Debug, Clone, Default)]
+pub enum Fixtures {
+    /// The recordings bundled with the crate.
+    #[default]
+    Bundled,
+    /// A directory of recordings, where e.g. `/v2/catalogs` is read from
+    /// `<dir>/v2/catalogs.json`.
+    Directory(PathBuf),
+}
+
+impl Fixtures {
+    fn load(&self, path: &str) -> Option<String> {
+        match self {
+            Fixtures::Bundled => BUNDLED
+                .iter()
+                .find(|(fixture, _)| *fixture == path)
+                .map(|(_, body)| body.to_string()),
+            Fixtures::Directory(dir) => {
+                let relative = Path::new(path.trim_start_matches('/'));
+                let is_safe = relative
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                if !is_safe {
+                    return None;
+                }
+                std::fs::read_to_string(dir.join(relative).with_extension("json")).ok()
+            }
+        }
+    }
+}
+
+/// Counts of the requests a [`MockServer`] received.
+#[derive(Debug, Default)]
+pub struct MockStats {
+    requests: AtomicUsize,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl MockStats {
+    /// Requests received so far.
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// Most requests being answered at the same time.
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    fn start(&self) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+    }
+
+    fn finish(&self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// An error response a [`MockServer`] gives instead of the recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub status: u16,
+    /// Value of the `Retry-After` header, if any.
+    pub retry_after: Option<String>,
+}
+
+impl Failure {
+    pub fn new(status: u16) -> Self {
+        Failure {
+            status,
+            retry_after: None,
+        }
+    }
+
+    pub fn retry_after(mut self, retry_after: impl Into<String>) -> Self {
+        self.retry_after = Some(retry_after.into());
+        self
+    }
+}
+
+/// Server answering API requests with recorded responses.
+pub struct MockServer {
+    listener: TcpListener,
+    fixtures: Fixtures,
+    failures: HashMap<String, VecDeque<Failure>>,
+    delay: Duration,
+    stats: Arc<MockStats>,
+}
+
+/// What the connection handlers share while the server runs.
+struct State {
+    fixtures: Fixtures,
+    failures: Mutex<HashMap<String, VecDeque<Failure>>>,
+    delay: Duration,
+    stats: Arc<MockStats>,
+}
+
+impl MockServer {
+    /// Bind the server to `addr`. Use port 0 to let the OS pick a free port.
+    pub async fn bind(addr: impl ToSocketAddrs, fixtures: Fixtures) -> io::Result<Self> {
+        Ok(MockServer {
+            listener: TcpListener::bind(addr).await?,
+            fixtures,
+            failures: HashMap::new(),
+            delay: Duration::ZERO,
+            stats: Arc::default(),
+        })
+    }
+
+    /// Answer the next requests for `path` with `failures`, in order, before going back to
+    /// the recording.
+    pub fn fail(mut self, path: &str, failures: impl IntoIterator<Item = Failure>) -> Self {
+        self.failures
+            .entry(path.to_string())
+            .or_default()
+            .extend(failures);
+        self
+    }
+
+    /// Wait `delay` before answering each request.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Counts of the requests received, which keep updating while the server runs.
+    pub fn stats(&self) -> Arc<MockStats> {
+        Arc::clone(&self.stats)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Base URL to hand to [`Client::with_base_url`](crate::Client::with_base_url).
+    pub fn url(&self) -> io::Result<String> {
+        Ok(format!("http://{}", self.local_addr()?))
+    }
+
+    /// Serve requests until the task is dropped.
+    pub async fn run(self) -> io::Result<()> {
+        let state = Arc::new(State {
+            fixtures: self.fixtures,
+            failures: Mutex::new(self.failures),
+            delay: self.delay,
+            stats: self.stats,
+        });
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                // A misbehaving client only affects its own connection.
+                let _ = handle_connection(stream, &state).await;
+            });
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: &State) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || request.len() > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    state.stats.start();
+    tokio::time::sleep(state.delay).await;
+    let result = respond(&mut stream, &String::from_utf8_lossy(&request), state).await;
+    state.stats.finish();
+    result
+}
+
+async fn respond(stream: &mut TcpStream, request: &str, state: &State) -> io::Result<()> {
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (method, target) = (request_line.next(), request_line.next().unwrap_or("/"));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let failure = state
+        .failures
+        .lock()
+        .unwrap()
+        .get_mut(path)
+        .and_then(VecDeque::pop_front);
+    let mut headers = String::new();
+    let (status, body) = if method != Some("GET") {
+        (405, error_body("Method not allowed"))
+    } else if let Some(failure) = failure {
+        if let Some(retry_after) = failure.retry_after {
+            headers = format!("Retry-After: {retry_after}\r\n");
+        }
+        (failure.status, error_body("Simulated failure"))
+    } else {
+        match state.fixtures.load(path) {
+            Some(body) => (200, apply_query(body, &parse_query(query))),
+            None => (404, error_body("Not found")),
+        }
+    };
+
+    let reason = reqwest::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "message": message }).to_string()
+}
+
+/// Narrow a recorded list response down the same way the API would.
+fn apply_query(body: String, query: &[(String, String)]) -> String {
+    let Ok(Value::Array(mut items)) = serde_json::from_str::<Value>(&body) else {
+        return body;
+    };
+
+    let mut offset = 0;
+    let mut limit = items.len();
+    let (mut latitude, mut longitude, mut radius) = (None, None, None);
+    for (key, value) in query {
+        match key.as_str() {
+            "dealer_ids" => {
+                let ids: Vec<_> = value.split(',').collect();
+                items.retain(|item| {
+                    item["dealer_id"]
+                        .as_str()
+                        .is_some_and(|id| ids.contains(&id))
+                });
+            }
+            "query" => {
+                let query = value.to_lowercase();
+                items.retain(|item| {
+                    item["heading"]
+                        .as_str()
+                        .is_some_and(|heading| heading.to_lowercase().contains(&query))
+                });
+            }
+            "r_lat" => latitude = value.parse().ok(),
+            "r_lng" => longitude = value.parse().ok(),
+            "r_radius" => radius = value.parse().ok(),
+            "offset" => offset = value.parse().unwrap_or(offset),
+            "limit" => limit = value.parse().unwrap_or(limit),
+            _ => (),
+        }
+    }
+
+    // Only items with coordinates, i.e. stores, are narrowed down by location.
+    if let (Some(latitude), Some(longitude), Some(radius)) = (latitude, longitude, radius) {
+        let location = Location::new(latitude, longitude, radius);
+        items.retain(
+            |item| match (item["latitude"].as_f64(), item["longitude"].as_f64()) {
+                (Some(lat), Some(lng)) => location.distance_to(lat, lng) <= radius as f64,
+                _ => true,
+            },
+        );
+    }
+
+    let page = items.into_iter().skip(offset).take(limit).collect();
+    Value::Array(page).to_string()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(input: &str) -> String {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = iter.by_ref().take(2).collect();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(decoded) => bytes.push(decoded),
+                    None => {
+                        bytes.push(b'%');
+                        bytes.extend(hex);
+                    }
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
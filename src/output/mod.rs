@@ -1,27 +1,73 @@
 mod rss;
 mod table;
 
-use crate::Offer;
+use crate::{
+    Dealer, Offer,
+    requests::{cache::DealerCacheStatus, category::Category, store::Store},
+};
+use anyhow::{Context, Result, bail};
 use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 
 /// Format to print offers in
-#[derive(Debug, ValueEnum, Clone, Copy)]
+#[derive(Debug, ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum OutputFormat {
     Json,
     Rss,
     Table,
 }
 
-/// Print offers in the specified format
-pub fn print_offers(offers: Vec<&Offer>, format: &OutputFormat) {
+/// Render offers in the specified format. `details` adds descriptions to tables, which
+/// the other formats always include.
+pub fn format_offers(offers: &[&Offer], format: OutputFormat, details: bool) -> Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string(offers).context("Failed to serialize offers"),
+        OutputFormat::Rss => rss::offers_as_rss(offers).context("Could not create rss feed"),
+        OutputFormat::Table => Ok(table::offers_as_table(offers, details)),
+    }
+}
+
+/// Render a list of dealers in the specified format, using `header` as the table heading
+pub fn format_dealers(dealers: &[Dealer], header: &str, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string(dealers).context("Failed to serialize dealers"),
+        OutputFormat::Rss => bail!("Unsupported output format for {}", header.to_lowercase()),
+        OutputFormat::Table => Ok(table::dealers_as_table(dealers, header)),
+    }
+}
+
+/// Render stores in the specified format
+pub fn format_stores(stores: &[Store], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string(stores).context("Failed to serialize stores"),
+        OutputFormat::Rss => bail!("Unsupported output format for stores"),
+        OutputFormat::Table => Ok(table::stores_as_table(stores)),
+    }
+}
+
+/// Render categories along with their number of offers in the specified format
+pub fn format_categories(categories: &[(Category, usize)], format: OutputFormat) -> Result<String> {
     match format {
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string(&offers).expect("dude what?"));
+            let categories: Vec<_> = categories
+                .iter()
+                .map(|(category, count)| serde_json::json!({ "category": category, "offers": count }))
+                .collect();
+            serde_json::to_string(&categories).context("Failed to serialize categories")
         }
-        OutputFormat::Rss => {
-            let rss = rss::offers_as_rss(offers).expect("Could not create rss feed");
-            println!("{}", rss);
+        OutputFormat::Rss => bail!("Unsupported output format for categories"),
+        OutputFormat::Table => Ok(table::categories_as_table(categories)),
+    }
+}
+
+/// Render how fresh the cached offers of each dealer are in the specified format
+pub fn format_cache_status(status: &[DealerCacheStatus], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_string(status).context("Failed to serialize cache status")
         }
-        OutputFormat::Table => table::print_as_table(offers),
+        OutputFormat::Rss => bail!("Unsupported output format for cache status"),
+        OutputFormat::Table => Ok(table::cache_status_as_table(status)),
     }
 }
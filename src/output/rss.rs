@@ -2,7 +2,7 @@ use crate::Offer;
 use std::fmt::Write;
 
 /// Create RSS feed with list of offers
-pub fn offers_as_rss(offers: Vec<&Offer>) -> Result<String, std::fmt::Error> {
+pub fn offers_as_rss(offers: &[&Offer]) -> Result<String, std::fmt::Error> {
     let mut output = String::new();
 
     writeln!(output, r#"<?xml version="1.0" encoding="UTF-8" ?>"#)?;
@@ -11,11 +11,46 @@ pub fn offers_as_rss(offers: Vec<&Offer>) -> Result<String, std::fmt::Error> {
 
     for offer in offers {
         writeln!(output, "\t<item>")?;
-        writeln!(
+        write!(
             output,
-            "\t\t<title>[{}] [{}] {}</title>",
-            offer.dealer, offer.price, offer.name
+            "\t\t<title>[{}] [{}] {}",
+            escape(&offer.dealer.name),
+            offer.price,
+            escape(&offer.name)
         )?;
+        if let (Some(pre_price), Some(savings), Some(discount)) =
+            (offer.pre_price, offer.savings, offer.discount)
+        {
+            write!(
+                output,
+                " (was {}, save {}, -{:.0}%)",
+                escape(&offer.format_amount(pre_price)),
+                escape(&offer.format_amount(savings)),
+                discount
+            )?;
+        }
+        writeln!(output, "</title>")?;
+        if let Some(description) = &offer.description {
+            writeln!(
+                output,
+                "\t\t<description>{}</description>",
+                escape(description)
+            )?;
+        }
+        if let Some(webshop) = &offer.webshop {
+            writeln!(output, "\t\t<link>{}</link>", escape(webshop))?;
+        }
+        if let Some(image) = offer.images.largest() {
+            writeln!(
+                output,
+                "\t\t<enclosure url=\"{}\" length=\"0\" type=\"{}\" />",
+                escape(image),
+                image_type(image)
+            )?;
+        }
+        for category in &offer.categories {
+            writeln!(output, "\t\t<category>{}</category>", category.label())?;
+        }
         writeln!(output, "\t\t<pubDate>{}</pubDate>", offer.run_from)?;
         writeln!(output, "\t</item>")?;
     }
@@ -25,3 +60,33 @@ pub fn offers_as_rss(offers: Vec<&Offer>) -> Result<String, std::fmt::Error> {
 
     Ok(output)
 }
+
+/// Escape text for use in XML elements and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Guess the MIME type of an image from its URL, defaulting to JPEG.
+fn image_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    if path.ends_with(".png") {
+        "image/png"
+    } else if path.ends_with(".webp") {
+        "image/webp"
+    } else if path.ends_with(".gif") {
+        "image/gif"
+    } else {
+        "image/jpeg"
+    }
+}
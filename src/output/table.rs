@@ -1,27 +1,156 @@
-use crate::Offer;
-use comfy_table::{ContentArrangement, Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use crate::{
+    Dealer, Offer,
+    requests::{cache::DealerCacheStatus, category::Category, store::Store},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::ValueEnum;
+use comfy_table::{
+    Cell, CellAlignment, ContentArrangement, Table, modifiers::UTF8_ROUND_CORNERS,
+    presets::UTF8_FULL,
+};
 
-/// Print offers as a table
-pub fn print_as_table(offers: Vec<&Offer>) {
+/// Render offers as a table, with a column of descriptions if `details` is set
+pub fn offers_as_table(offers: &[&Offer], details: bool) -> String {
+    let mut header = vec![
+        "Period",
+        "Dealer",
+        "Product",
+        "Count",
+        "Price",
+        "Savings",
+        "Cost/unit",
+        "Size",
+    ];
+    if details {
+        header.push("Details");
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_width(if details { 140 } else { 100 })
+        .set_header(header);
+
+    for offer in offers.iter() {
+        let mut row = offer.to_table_entry();
+        if details {
+            row.push(Cell::new(offer.details()));
+        }
+        table.add_row(row);
+    }
+
+    table.to_string()
+}
+
+/// Render dealers as a table of names and ids
+pub fn dealers_as_table(dealers: &[Dealer], header: &str) -> String {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec![header, "Id"]);
+
+    for dealer in dealers {
+        table.add_row(vec![dealer.name.as_str(), dealer.id.as_str()]);
+    }
+
+    table.to_string()
+}
+
+/// Render stores as a table
+pub fn stores_as_table(stores: &[Store]) -> String {
     let mut table = Table::new();
     table
         .load_preset(UTF8_FULL)
         .apply_modifier(UTF8_ROUND_CORNERS)
         .set_content_arrangement(ContentArrangement::Dynamic)
         .set_width(100)
+        .set_header(vec!["Dealer", "Address", "Distance", "Opening hours"]);
+
+    for store in stores {
+        table.add_row(vec![
+            Cell::new(store.dealer.to_string()),
+            Cell::new(store.address()),
+            Cell::new(format!("{:.1} km", store.distance / 1000.0))
+                .set_alignment(CellAlignment::Right),
+            Cell::new(store.opening_hours_summary()),
+        ]);
+    }
+
+    table.to_string()
+}
+
+/// Render categories as a table of names, ids and number of offers
+pub fn categories_as_table(categories: &[(Category, usize)]) -> String {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec!["Category", "Id", "Offers"]);
+
+    for (category, count) in categories {
+        let id = category
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        table.add_row(vec![
+            Cell::new(category.label()),
+            Cell::new(id),
+            Cell::new(count).set_alignment(CellAlignment::Right),
+        ]);
+    }
+
+    table.to_string()
+}
+
+/// Render the freshness of cached offers as a table, one row per dealer
+pub fn cache_status_as_table(status: &[DealerCacheStatus]) -> String {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
         .set_header(vec![
-            "Period",
             "Dealer",
-            "Product",
-            "Count",
-            "Price",
-            "Cost/unit",
-            "Weight",
+            "Catalogs",
+            "Offers",
+            "Checked",
+            "Oldest catalog",
+            "Valid until",
+            "Status",
         ]);
 
-    for offer in offers.iter() {
-        table.add_row(offer.to_table_entry());
+    let now = Utc::now();
+    let age = |time: Option<DateTime<Utc>>| {
+        time.map_or("never".to_string(), |time| format_age(now - time))
+    };
+    for dealer in status {
+        table.add_row(vec![
+            Cell::new(dealer.dealer.to_string()),
+            Cell::new(dealer.catalogs).set_alignment(CellAlignment::Right),
+            Cell::new(dealer.offers).set_alignment(CellAlignment::Right),
+            Cell::new(age(dealer.checked)),
+            Cell::new(age(dealer.oldest_catalog)),
+            Cell::new(
+                dealer
+                    .valid_until
+                    .map_or("-".to_string(), |date| date.format("%d/%m").to_string()),
+            ),
+            Cell::new(if dealer.outdated { "outdated" } else { "fresh" }),
+        ]);
     }
 
-    println!("{}", table);
+    table.to_string()
+}
+
+/// Describe how long ago something happened, e.g. `3 h ago`.
+fn format_age(age: TimeDelta) -> String {
+    match age {
+        age if age.num_minutes() < 1 => "just now".to_string(),
+        age if age.num_hours() < 1 => format!("{} min ago", age.num_minutes()),
+        age if age.num_days() < 1 => format!("{} h ago", age.num_hours()),
+        age if age.num_days() == 1 => "1 day ago".to_string(),
+        age => format!("{} days ago", age.num_days()),
+    }
 }
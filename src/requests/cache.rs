@@ -0,0 +1,216 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::{
+    catalog::Catalog, client::Client, dealer::Dealer, location::Location, offer::RetrievedOffers,
+};
+use crate::{Offer, error::Result};
+
+/// Offers of favorite dealers, kept per dealer and catalog, so that only dealers whose
+/// catalogs may have changed are asked again, and only new catalogs are retrieved.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OfferCache {
+    /// By dealer id.
+    dealers: BTreeMap<String, DealerCache>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DealerCache {
+    dealer: Dealer,
+    /// When the catalogs of the dealer were last compared with the API.
+    checked: DateTime<Utc>,
+    /// Location the catalogs were retrieved for.
+    location: Option<Location>,
+    /// By catalog id.
+    catalogs: BTreeMap<String, CachedCatalog>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedCatalog {
+    catalog: Catalog,
+    retrieved: DateTime<Utc>,
+    offers: Vec<Offer>,
+}
+
+/// How fresh the cached offers of a dealer are.
+#[derive(Debug, Serialize)]
+pub struct DealerCacheStatus {
+    pub dealer: Dealer,
+    pub catalogs: usize,
+    pub offers: usize,
+    /// When the catalogs were last compared with the API, `None` if never retrieved.
+    pub checked: Option<DateTime<Utc>>,
+    /// When the offers of the oldest catalog were retrieved.
+    pub oldest_catalog: Option<DateTime<Utc>>,
+    /// Last day every catalog is still valid.
+    pub valid_until: Option<NaiveDate>,
+    /// Whether the catalogs will be checked on the next retrieval.
+    pub outdated: bool,
+}
+
+/// Catalogs of a dealer as currently listed by the API, along with the offers of the
+/// catalogs that were not cached yet.
+#[derive(Debug)]
+pub struct DealerUpdate {
+    pub catalogs: Vec<Catalog>,
+    /// Whether the list of catalogs was cut short at
+    /// [`ClientOptions::max_results`](super::client::ClientOptions::max_results).
+    pub catalogs_truncated: bool,
+    pub retrieved: Vec<(String, RetrievedOffers)>,
+}
+
+impl DealerUpdate {
+    /// List the catalogs of `dealer`, retrieving the offers of those not in `cached`.
+    pub async fn retrieve(
+        client: &Client,
+        dealer: &Dealer,
+        location: Option<&Location>,
+        cached: &HashSet<String>,
+    ) -> Result<DealerUpdate> {
+        let catalogs = Catalog::retrieve_for_dealer(client, dealer, location).await?;
+        let tasks: Vec<_> = catalogs
+            .items
+            .iter()
+            .filter(|catalog| !cached.contains(&catalog.id))
+            .map(|catalog| {
+                let catalog = catalog.clone();
+                let client = client.clone();
+                tokio::spawn(async move {
+                    let mut retrieved = catalog.retrieve_offers(&client).await?;
+                    retrieved.dedup();
+                    Ok::<_, crate::Error>((catalog.id, retrieved))
+                })
+            })
+            .collect();
+
+        let mut retrieved = Vec::with_capacity(tasks.len());
+        for result in future::join_all(tasks).await {
+            retrieved.push(result??);
+        }
+        Ok(DealerUpdate {
+            catalogs: catalogs.items,
+            catalogs_truncated: catalogs.truncated,
+            retrieved,
+        })
+    }
+}
+
+impl OfferCache {
+    /// Whether the catalogs of `dealer` must be compared with the API, because they
+    /// were never retrieved, were retrieved for another location or not yet today.
+    pub fn outdated(&self, dealer: &Dealer, location: Option<Location>, today: NaiveDate) -> bool {
+        self.dealers
+            .get(&dealer.id)
+            .is_none_or(|cached| cached.location != location || cached.checked.date_naive() < today)
+    }
+
+    /// Ids of the cached catalogs of `dealer` for `location`, which need not be
+    /// retrieved again.
+    pub fn catalog_ids(&self, dealer: &Dealer, location: Option<Location>) -> HashSet<String> {
+        self.dealers
+            .get(&dealer.id)
+            .filter(|cached| cached.location == location)
+            .map(|cached| cached.catalogs.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Replace the catalogs of `dealer` with those currently listed, keeping the offers
+    /// of catalogs that were already cached. Catalogs no longer listed are dropped.
+    pub fn update(
+        &mut self,
+        dealer: &Dealer,
+        location: Option<Location>,
+        update: DealerUpdate,
+        now: DateTime<Utc>,
+    ) {
+        let mut previous = self
+            .dealers
+            .remove(&dealer.id)
+            .filter(|cached| cached.location == location)
+            .map(|cached| cached.catalogs)
+            .unwrap_or_default();
+        let mut retrieved: HashMap<_, _> = update.retrieved.into_iter().collect();
+        let catalogs = update
+            .catalogs
+            .into_iter()
+            .filter_map(|catalog| {
+                let cached = match retrieved.remove(&catalog.id) {
+                    Some(retrieved) => CachedCatalog {
+                        retrieved: now,
+                        offers: retrieved.offers,
+                        catalog,
+                    },
+                    // Keep the offers, but take up changes to the catalog itself.
+                    None => {
+                        let cached = previous.remove(&catalog.id)?;
+                        CachedCatalog { catalog, ..cached }
+                    }
+                };
+                Some((cached.catalog.id.clone(), cached))
+            })
+            .collect();
+        self.dealers.insert(
+            dealer.id.clone(),
+            DealerCache {
+                dealer: dealer.clone(),
+                checked: now,
+                location,
+                catalogs,
+            },
+        );
+    }
+
+    /// Forget the offers of `dealer`.
+    pub fn remove(&mut self, dealer: &Dealer) {
+        self.dealers.remove(&dealer.id);
+    }
+
+    /// Forget the offers of dealers for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Dealer) -> bool) {
+        self.dealers.retain(|_, cached| keep(&cached.dealer));
+    }
+
+    /// Offers of catalogs still valid on `today`. The same offer may appear in several
+    /// catalogs of a dealer.
+    pub fn offers(&self, today: NaiveDate) -> impl Iterator<Item = &Offer> {
+        self.dealers
+            .values()
+            .flat_map(|cached| cached.catalogs.values())
+            .filter(move |cached| !cached.catalog.expired_on(today))
+            .flat_map(|cached| &cached.offers)
+    }
+
+    /// How fresh the cached offers of each of `dealers` are.
+    pub fn status(
+        &self,
+        dealers: &[Dealer],
+        location: Option<Location>,
+        today: NaiveDate,
+    ) -> Vec<DealerCacheStatus> {
+        dealers
+            .iter()
+            .map(|dealer| {
+                let cached = self.dealers.get(&dealer.id);
+                let catalogs: Vec<_> = cached
+                    .iter()
+                    .flat_map(|cached| cached.catalogs.values())
+                    .filter(|cached| !cached.catalog.expired_on(today))
+                    .collect();
+                DealerCacheStatus {
+                    dealer: dealer.clone(),
+                    catalogs: catalogs.len(),
+                    offers: catalogs.iter().map(|cached| cached.offers.len()).sum(),
+                    checked: cached.map(|cached| cached.checked),
+                    oldest_catalog: catalogs.iter().map(|cached| cached.retrieved).min(),
+                    valid_until: catalogs
+                        .iter()
+                        .filter_map(|cached| cached.catalog.run_till)
+                        .min(),
+                    outdated: self.outdated(dealer, location, today),
+                }
+            })
+            .collect()
+    }
+}
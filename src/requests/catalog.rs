@@ -0,0 +1,76 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    client::{Client, Paginated},
+    dealer::Dealer,
+    deserialize::{RawCatalog, deserialize_catalog, deserialize_hotspots},
+    location::Location,
+    offer::RetrievedOffers,
+};
+use crate::error::{Error, Result};
+
+/// A catalog (weekly leaflet) published by a dealer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Catalog {
+    pub id: String,
+    pub dealer: Dealer,
+    pub label: Option<String>,
+    pub run_from: Option<NaiveDate>,
+    /// Last day the catalog is valid, if the dealer states it.
+    pub run_till: Option<NaiveDate>,
+}
+
+impl Catalog {
+    /// Fetch the catalogs currently published by `dealer`, limited to catalogs valid at
+    /// `location` if given.
+    pub async fn retrieve_for_dealer(
+        client: &Client,
+        dealer: &Dealer,
+        location: Option<&Location>,
+    ) -> Result<Paginated<Catalog>> {
+        let location = location.map(Location::query);
+        let mut query = vec![("dealer_ids", dealer.id.as_str())];
+        query.extend(
+            location
+                .iter()
+                .flatten()
+                .map(|(key, value)| (*key, value.as_str())),
+        );
+        let raw = client
+            .get_paginated::<RawCatalog>("/v2/catalogs", &query)
+            .await?;
+        Ok(Paginated {
+            items: raw
+                .items
+                .into_iter()
+                .map(|raw| deserialize_catalog(raw, dealer))
+                .collect(),
+            truncated: raw.truncated,
+        })
+    }
+
+    /// Fetch the offers of this catalog.
+    pub async fn retrieve_offers(&self, client: &Client) -> Result<RetrievedOffers> {
+        let path = format!("/v2/catalogs/{}/hotspots", self.id);
+        let hotspots = client
+            .get_paginated(&path, &[])
+            .await
+            .map_err(|source| Error::Catalog {
+                catalog_id: self.id.clone(),
+                source: Box::new(source),
+            })?;
+        let mut retrieved = deserialize_hotspots(hotspots.items, &self.dealer, &self.id);
+        if hotspots.truncated {
+            retrieved
+                .truncated
+                .push(format!("catalog {} of {}", self.id, self.dealer));
+        }
+        Ok(retrieved)
+    }
+
+    /// Whether the catalog ran out before `date`.
+    pub fn expired_on(&self, date: NaiveDate) -> bool {
+        self.run_till.is_some_and(|run_till| run_till < date)
+    }
+}
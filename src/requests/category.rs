@@ -0,0 +1,336 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::matcher::normalize;
+
+/// Product category of an offer, as given by the API or guessed from its name.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    Meat,
+    Fish,
+    Dairy,
+    Bakery,
+    Produce,
+    Frozen,
+    Pantry,
+    Beverages,
+    Snacks,
+    Household,
+    PersonalCare,
+    Flowers,
+    Other,
+}
+
+/// Words identifying a category, normalized as by [`normalize`]. Words of two letters
+/// must match a whole word, longer ones the start of a word, and from four letters on
+/// anywhere in a word, so Danish compounds such as "letmælk" are recognized. The
+/// longest keyword found in a word decides, so "vingummi" is not taken for "vin".
+const KEYWORDS: &[(Category, &[&str])] = &[
+    (
+        Category::Meat,
+        &[
+            "kod",
+            "okse",
+            "svin",
+            "kylling",
+            "kalkun",
+            "flaesk",
+            "bacon",
+            "polse",
+            "polser",
+            "skinke",
+            "hakke",
+            "bof",
+            "steak",
+            "morbrad",
+            "frikadelle",
+            "postej",
+            "salami",
+            "lam",
+            "meat",
+            "beef",
+            "pork",
+            "chicken",
+        ],
+    ),
+    (
+        Category::Fish,
+        &[
+            "fisk",
+            "laks",
+            "torsk",
+            "tun",
+            "rejer",
+            "sild",
+            "makrel",
+            "rodspaette",
+            "fish",
+            "salmon",
+            "shrimp",
+        ],
+    ),
+    (
+        Category::Dairy,
+        &[
+            "maelk",
+            "smor",
+            "ost",
+            "yoghurt",
+            "skyr",
+            "flode",
+            "creme",
+            "aeg",
+            "frilandsaeg",
+            "kaernemaelk",
+            "milk",
+            "butter",
+            "cheese",
+            "eggs",
+            "mejeri",
+        ],
+    ),
+    (
+        Category::Bakery,
+        &[
+            "brod",
+            "rugbrod",
+            "boller",
+            "baguette",
+            "kage",
+            "wienerbrod",
+            "toast",
+            "bread",
+            "bageri",
+        ],
+    ),
+    (
+        Category::Produce,
+        &[
+            "aebler",
+            "bananer",
+            "appelsiner",
+            "kartofler",
+            "gulerodder",
+            "vindrue",
+            "tomater",
+            "agurk",
+            "salat",
+            "log",
+            "melon",
+            "frugt",
+            "gront",
+            "gronsager",
+            "baer",
+            "fruit",
+            "vegetables",
+        ],
+    ),
+    (
+        Category::Frozen,
+        &["frost", "frossen", "frosne", "is", "frozen"],
+    ),
+    (
+        Category::Pantry,
+        &[
+            "pasta",
+            "ris",
+            "mel",
+            "sukker",
+            "olie",
+            "kaffe",
+            "te",
+            "konserves",
+            "havregryn",
+            "mysli",
+            "musli",
+            "ketchup",
+            "sauce",
+            "krydderi",
+            "coffee",
+            "kolonial",
+        ],
+    ),
+    (
+        Category::Beverages,
+        &[
+            "sodavand", "cola", "vand", "juice", "ol", "vin", "saft", "drikke", "beer", "wine",
+            "soda",
+        ],
+    ),
+    (
+        Category::Snacks,
+        &[
+            "chips",
+            "slik",
+            "chokolade",
+            "kiks",
+            "nodder",
+            "popcorn",
+            "vingummi",
+            "snacks",
+            "candy",
+        ],
+    ),
+    (
+        Category::Household,
+        &[
+            "vaskemiddel",
+            "opvask",
+            "toiletpapir",
+            "kokkenrulle",
+            "rengoring",
+            "skyllemiddel",
+            "poser",
+            "batterier",
+            "lampe",
+            "husholdning",
+        ],
+    ),
+    (
+        Category::PersonalCare,
+        &[
+            "shampoo",
+            "balsam",
+            "saebe",
+            "tandpasta",
+            "deodorant",
+            "bleer",
+            "barberskrab",
+            "personlig",
+            "pleje",
+        ],
+    ),
+    (
+        Category::Flowers,
+        &[
+            "tulipaner",
+            "roser",
+            "blomster",
+            "buket",
+            "planter",
+            "flowers",
+        ],
+    ),
+];
+
+impl Category {
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Meat => "Meat",
+            Category::Fish => "Fish",
+            Category::Dairy => "Dairy and eggs",
+            Category::Bakery => "Bakery",
+            Category::Produce => "Fruit and vegetables",
+            Category::Frozen => "Frozen",
+            Category::Pantry => "Pantry",
+            Category::Beverages => "Beverages",
+            Category::Snacks => "Snacks and sweets",
+            Category::Household => "Household",
+            Category::PersonalCare => "Personal care",
+            Category::Flowers => "Flowers and plants",
+            Category::Other => "Other",
+        }
+    }
+
+    /// Resolve a category from the API by its id or name, in English or Danish.
+    pub(crate) fn from_api(name: &str) -> Option<Category> {
+        let name = normalize(name.trim());
+        Category::value_variants()
+            .iter()
+            .copied()
+            .find(|category| {
+                category
+                    .to_possible_value()
+                    .is_some_and(|value| value.matches(&name, true))
+            })
+            .or_else(|| Category::guess(&name))
+    }
+
+    /// Guess the category of an offer from its name, falling back to its description.
+    pub fn classify(name: &str, description: Option<&str>) -> Category {
+        Category::guess(name)
+            .or_else(|| description.and_then(Category::guess))
+            .unwrap_or(Category::Other)
+    }
+
+    fn guess(text: &str) -> Option<Category> {
+        let text = normalize(text);
+        let words: Vec<_> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        // The first word decides, as in "Kaffe, formalet" or "Hakket oksekød".
+        words.iter().find_map(|word| {
+            KEYWORDS
+                .iter()
+                .flat_map(|(category, keywords)| {
+                    keywords.iter().map(move |keyword| (*category, *keyword))
+                })
+                .filter(|(_, keyword)| match keyword.len() {
+                    0..=2 => word == keyword,
+                    3 => word.starts_with(keyword),
+                    _ => word.contains(keyword),
+                })
+                .min_by_key(|(_, keyword)| std::cmp::Reverse(keyword.len()))
+                .map(|(category, _)| category)
+        })
+    }
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_from_the_first_recognized_word() {
+        assert_eq!(Category::guess("Kaffe, formalet"), Some(Category::Pantry));
+        assert_eq!(
+            Category::guess("Hakket oksekød 8-12%"),
+            Some(Category::Meat)
+        );
+        assert_eq!(Category::guess("Økologisk letmælk"), Some(Category::Dairy));
+        assert_eq!(Category::guess("Tulipaner"), Some(Category::Flowers));
+        assert_eq!(Category::guess("Gavekort"), None);
+    }
+
+    #[test]
+    fn prefers_the_longest_keyword() {
+        assert_eq!(Category::guess("Vin"), Some(Category::Beverages));
+        assert_eq!(Category::guess("Vingummi"), Some(Category::Snacks));
+        assert_eq!(Category::guess("Vindruer"), Some(Category::Produce));
+        assert_eq!(Category::guess("Lammekølle"), Some(Category::Meat));
+        assert_eq!(Category::guess("Lampe"), Some(Category::Household));
+    }
+
+    #[test]
+    fn matches_danish_letters_after_normalizing() {
+        assert_eq!(Category::guess("Rødspætte"), Some(Category::Fish));
+        assert_eq!(Category::guess("Blandede nødder"), Some(Category::Snacks));
+    }
+
+    #[test]
+    fn short_keywords_match_whole_words_only() {
+        assert_eq!(Category::guess("Is"), Some(Category::Frozen));
+        assert_eq!(Category::guess("Iskaffe"), Some(Category::Pantry));
+        assert_eq!(Category::guess("Te"), Some(Category::Pantry));
+        assert_eq!(Category::guess("Termokande"), None);
+    }
+
+    #[test]
+    fn classifies_by_description_when_the_name_is_unknown() {
+        assert_eq!(
+            Category::classify("Tilbud", Some("Frisk laks")),
+            Category::Fish
+        );
+        assert_eq!(Category::classify("Tilbud", None), Category::Other);
+    }
+}
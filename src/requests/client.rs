@@ -0,0 +1,455 @@
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::error::{Error, Result};
+
+/// Base URL of the public eTilbudsavis API.
+pub const DEFAULT_API_URL: &str = "https://squid-api.tjek.com";
+
+/// Largest page the API hands out for paginated endpoints.
+const PAGE_SIZE: usize = 100;
+
+/// Longest `Retry-After` the client is willing to honour.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Settings for a [`Client`].
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Base URL of the API, e.g. a local mock server.
+    pub base_url: String,
+    /// Timeout for a single request, including reading the body.
+    pub timeout: Duration,
+    /// Timeout for establishing a connection.
+    pub connect_timeout: Duration,
+    /// How many times a failed request is retried.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every following retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries.
+    pub max_backoff: Duration,
+    /// Upper bound on requests in flight at once, across every clone of the client.
+    pub max_concurrent_requests: usize,
+    /// Upper bound on items retrieved from a single paginated endpoint.
+    pub max_results: usize,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            base_url: DEFAULT_API_URL.to_string(),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            max_concurrent_requests: 8,
+            max_results: 1000,
+        }
+    }
+}
+
+/// Items of a list endpoint, retrieved page by page.
+#[derive(Debug)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Whether retrieval stopped at [`ClientOptions::max_results`] while the API may
+    /// have had more items.
+    pub truncated: bool,
+}
+
+/// Client used for every request made against the eTilbudsavis API.
+///
+/// Cloning is cheap, and clones share the underlying connection pool and the limit
+/// on concurrent requests.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    options: Arc<ClientOptions>,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::from_options(ClientOptions::default()).expect("default client options are valid")
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a client that sends requests to `base_url` instead of the public API,
+    /// e.g. a local mock server.
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self::from_options(ClientOptions {
+            base_url: base_url.into(),
+            ..ClientOptions::default()
+        })
+        .expect("default client options are valid")
+    }
+
+    pub fn from_options(mut options: ClientOptions) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(options.timeout)
+            .connect_timeout(options.connect_timeout)
+            .build()?;
+        options.base_url = options.base_url.trim_end_matches('/').to_string();
+        let permits = Arc::new(Semaphore::new(options.max_concurrent_requests.max(1)));
+
+        Ok(Client {
+            http,
+            options: Arc::new(options),
+            permits,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.options.base_url
+    }
+
+    /// Send a GET request for `path` relative to the base URL and decode the JSON
+    /// body of a successful response.
+    pub(crate) async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let url = format!("{}{}", self.options.base_url, path);
+        let (response, _permit) = self.get(&url, query).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Status { url, status });
+        }
+
+        response
+            .json::<T>()
+            .await
+            .map_err(|source| Error::Decode { url, source })
+    }
+
+    /// Send GET requests for every page of a list endpoint, until the API runs out of
+    /// items or [`ClientOptions::max_results`] is reached, in which case the items are
+    /// marked as truncated.
+    pub(crate) async fn get_paginated<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Paginated<T>> {
+        let max_results = self.options.max_results;
+        // One item more than wanted tells whether the API had more to give.
+        let wanted = max_results.saturating_add(1);
+        let mut items = Vec::new();
+        while items.len() < wanted {
+            let limit = PAGE_SIZE.min(wanted - items.len());
+            let offset = items.len().to_string();
+            let limit_param = limit.to_string();
+            let mut page_query = query.to_vec();
+            page_query.extend([("offset", offset.as_str()), ("limit", limit_param.as_str())]);
+
+            let page: Vec<T> = self.get_json(path, &page_query).await?;
+            let is_last_page = page.len() < limit;
+            items.extend(page);
+            if is_last_page {
+                break;
+            }
+        }
+        let truncated = items.len() > max_results;
+        items.truncate(max_results);
+        Ok(Paginated { items, truncated })
+    }
+
+    /// Send a GET request, retrying transient failures with exponential backoff.
+    ///
+    /// The returned permit must be held until the body has been read.
+    async fn get(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<(reqwest::Response, SemaphorePermit<'_>)> {
+        let mut attempt = 0;
+        loop {
+            let permit = self
+                .permits
+                .acquire()
+                .await
+                .expect("request semaphore is never closed");
+            let response = self
+                .http
+                .get(url)
+                .query(query)
+                .header("Accept", "application/json")
+                .send()
+                .await;
+
+            let retry_after = match &response {
+                Ok(response) if is_transient(response.status()) => retry_after(response.headers()),
+                Err(err) if err.is_connect() || err.is_timeout() => None,
+                _ => return Ok((response?, permit)),
+            };
+            if attempt >= self.options.max_retries {
+                return Ok((response?, permit));
+            }
+
+            drop(permit);
+            tokio::time::sleep(retry_after.unwrap_or_else(|| self.backoff(attempt))).await;
+            attempt += 1;
+        }
+    }
+
+    /// Exponential backoff with jitter, so concurrent retries do not line up.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .options
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.options.max_backoff);
+        // Every `RandomState` is seeded randomly, so hashing nothing with a fresh one is a
+        // cheap random number without pulling in a dependency. This is intentional.
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        delay.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parse a `Retry-After` header given either in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    let delay = match value.trim().parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            (date.to_utc() - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        }
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Failure, Fixtures, MockServer, MockStats};
+    use reqwest::header::{HeaderValue, RETRY_AFTER};
+    use std::{path::PathBuf, time::Instant};
+
+    /// A fixture directory serving `/v2/items` as the numbers `0..count`.
+    fn numbered_items(name: &str, count: usize) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("etb-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("v2")).unwrap();
+        let items: Vec<_> = (0..count).collect();
+        std::fs::write(
+            dir.join("v2/items.json"),
+            serde_json::to_string(&items).unwrap(),
+        )
+        .unwrap();
+        dir
+    }
+
+    async fn bind(fixtures: Fixtures) -> MockServer {
+        MockServer::bind(("127.0.0.1", 0), fixtures).await.unwrap()
+    }
+
+    async fn serve(server: MockServer, options: ClientOptions) -> (Client, Arc<MockStats>) {
+        let stats = server.stats();
+        let client = Client::from_options(ClientOptions {
+            base_url: server.url().unwrap(),
+            ..options
+        })
+        .unwrap();
+        tokio::spawn(server.run());
+        (client, stats)
+    }
+
+    async fn paginate(name: &str, count: usize, max_results: usize) -> (Paginated<usize>, usize) {
+        let dir = numbered_items(name, count);
+        let options = ClientOptions {
+            max_results,
+            ..ClientOptions::default()
+        };
+        let (client, stats) = serve(bind(Fixtures::Directory(dir.clone())).await, options).await;
+        let items = client.get_paginated("/v2/items", &[]).await;
+        std::fs::remove_dir_all(dir).unwrap();
+        (items.unwrap(), stats.requests())
+    }
+
+    #[tokio::test]
+    async fn retrieves_every_page() {
+        let (items, requests) = paginate("pages", 250, 1000).await;
+        assert_eq!(items.items, (0..250).collect::<Vec<_>>());
+        assert!(!items.truncated);
+        assert_eq!(requests, 3);
+    }
+
+    #[tokio::test]
+    async fn stops_at_max_results() {
+        let (items, requests) = paginate("capped", 250, 120).await;
+        assert_eq!(items.items, (0..120).collect::<Vec<_>>());
+        assert!(items.truncated);
+        assert_eq!(requests, 2);
+    }
+
+    #[tokio::test]
+    async fn exactly_max_results_is_not_truncated() {
+        let (items, _) = paginate("exact", 200, 200).await;
+        assert_eq!(items.items.len(), 200);
+        assert!(!items.truncated);
+
+        let (items, _) = paginate("one-more", 201, 200).await;
+        assert_eq!(items.items.len(), 200);
+        assert!(items.truncated);
+    }
+
+    /// Options retrying quickly, so tests do not wait on the backoff.
+    fn quick_retries() -> ClientOptions {
+        ClientOptions {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            ..ClientOptions::default()
+        }
+    }
+
+    async fn get_dealers(
+        server: MockServer,
+        options: ClientOptions,
+    ) -> (Result<Vec<serde_json::Value>>, usize) {
+        let (client, stats) = serve(server, options).await;
+        let result = client.get_json("/v2/dealers", &[]).await;
+        (result, stats.requests())
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = bind(Fixtures::Bundled)
+            .await
+            .fail("/v2/dealers", [Failure::new(503), Failure::new(500)]);
+        let (dealers, requests) = get_dealers(server, quick_retries()).await;
+        assert!(!dealers.unwrap().is_empty());
+        assert_eq!(requests, 3);
+    }
+
+    #[tokio::test]
+    async fn retries_too_many_requests() {
+        let server = bind(Fixtures::Bundled)
+            .await
+            .fail("/v2/dealers", [Failure::new(429)]);
+        let (dealers, requests) = get_dealers(server, quick_retries()).await;
+        assert!(dealers.is_ok());
+        assert_eq!(requests, 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_last_attempt() {
+        let server = bind(Fixtures::Bundled)
+            .await
+            .fail("/v2/dealers", vec![Failure::new(503); 3]);
+        let options = ClientOptions {
+            max_retries: 2,
+            ..quick_retries()
+        };
+        let (dealers, requests) = get_dealers(server, options).await;
+        match dealers {
+            Err(Error::Status { status, .. }) => {
+                assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE)
+            }
+            other => panic!("expected a 503, got {other:?}"),
+        }
+        assert_eq!(requests, 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let server = bind(Fixtures::Bundled)
+            .await
+            .fail("/v2/dealers", [Failure::new(404)]);
+        let (dealers, requests) = get_dealers(server, quick_retries()).await;
+        assert!(matches!(
+            dealers,
+            Err(Error::Status {
+                status: StatusCode::NOT_FOUND,
+                ..
+            })
+        ));
+        assert_eq!(requests, 1);
+    }
+
+    #[tokio::test]
+    async fn honours_retry_after_in_seconds() {
+        let server = bind(Fixtures::Bundled)
+            .await
+            .fail("/v2/dealers", [Failure::new(503).retry_after("1")]);
+        let start = Instant::now();
+        let (dealers, requests) = get_dealers(server, quick_retries()).await;
+        assert!(dealers.is_ok());
+        assert_eq!(requests, 2);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn honours_retry_after_as_date() {
+        // A date in the past means retrying right away rather than after the long backoff.
+        let server = bind(Fixtures::Bundled).await.fail(
+            "/v2/dealers",
+            [Failure::new(429).retry_after("Wed, 21 Oct 2015 07:28:00 GMT")],
+        );
+        let options = ClientOptions {
+            initial_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(30),
+            ..ClientOptions::default()
+        };
+        let (dealers, requests) =
+            tokio::time::timeout(Duration::from_secs(5), get_dealers(server, options))
+                .await
+                .expect("Retry-After date was not honoured");
+        assert!(dealers.is_ok());
+        assert_eq!(requests, 2);
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_requests() {
+        let server = bind(Fixtures::Bundled)
+            .await
+            .with_delay(Duration::from_millis(50));
+        let options = ClientOptions {
+            max_concurrent_requests: 2,
+            ..ClientOptions::default()
+        };
+        let (client, stats) = serve(server, options).await;
+        let requests = (0..6).map(|_| client.get_json::<serde_json::Value>("/v2/dealers", &[]));
+        for result in futures::future::join_all(requests).await {
+            result.unwrap();
+        }
+        assert_eq!(stats.requests(), 6);
+        assert_eq!(stats.max_in_flight(), 2);
+    }
+
+    fn retry_after_header(value: &str) -> Option<Duration> {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        retry_after(&headers)
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        assert_eq!(retry_after_header("7"), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after_header("3600"), Some(MAX_RETRY_AFTER));
+        assert_eq!(
+            retry_after_header("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after_header("soon"), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use super::{dealer::Dealer, location::Location, offer::SortKey, storage::write_atomic};
+use crate::OutputFormat;
+
+/// Version of the config file written by this version of etb. Files without a version
+/// are version 1.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Settings chosen by the user, kept in `config.toml` in the config directory so that
+/// clearing the cache directory only loses fetched data.
+///
+/// ```toml
+/// format = "table"
+/// sort = ["dealer", "unit-price"]
+/// favorites = ["Netto", { name = "Løvbjerg", id = "65caN" }]
+/// ```
+///
+/// Only built-in dealers can be given by name alone, any other dealer needs the id shown
+/// by `etb dealers`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Schema version, see [`CONFIG_VERSION`].
+    pub version: u32,
+    /// Output format used when `--format` is not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+    /// Sort order used when `--sort` is not given.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<SortKey>,
+    /// Reverse the configured sort order.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub reverse: bool,
+    /// Location offers are retrieved for, unless overridden with `--near`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    pub favorites: BTreeSet<Dealer>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            format: None,
+            sort: Vec::new(),
+            reverse: false,
+            location: None,
+            favorites: BTreeSet::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Where the config is kept, e.g. `~/.config/etilbudsavis-cli/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("etilbudsavis-cli/config.toml"))
+    }
+
+    /// Read the config, or `None` if there is none yet.
+    ///
+    /// Unlike the cache, a config that cannot be read is not set aside, as it holds
+    /// settings made by hand that cannot be fetched again. The error names the file so
+    /// it can be fixed.
+    pub fn load() -> Result<Option<Config>> {
+        let path = Config::path().context("Could not find config dir")?;
+        let data = match std::fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let config: Config = toml::from_str(&data)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        if config.version == 0 || config.version > CONFIG_VERSION {
+            bail!(
+                "Config file {} was written by another version of etb (version {}, expected at most {CONFIG_VERSION})",
+                path.display(),
+                config.version
+            );
+        }
+        Ok(Some(config))
+    }
+
+    /// Write the config, as the current [`CONFIG_VERSION`].
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Config::path().context("Could not find config dir")?)
+    }
+
+    pub(crate) fn save_to(&self, path: &Path) -> Result<()> {
+        write_atomic(path, toml::to_string(self)?.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_favorites_by_name_or_id() {
+        let config: Config =
+            toml::from_str(r#"favorites = ["Netto", { name = "Løvbjerg", id = "65caN" }]"#)
+                .unwrap();
+        assert_eq!(
+            config.favorites,
+            [
+                Dealer::new("9ba51", "Netto"),
+                Dealer::new("65caN", "Løvbjerg")
+            ]
+            .into()
+        );
+    }
+
+    #[test]
+    fn explains_why_a_favorite_name_is_unknown() {
+        let err = toml::from_str::<Config>(r#"favorites = ["Løvbjerg"]"#).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("only built-in dealers"), "{message}");
+        assert!(message.contains(r#"name = "Løvbjerg""#), "{message}");
+    }
+}
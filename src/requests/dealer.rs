@@ -1,203 +1,152 @@
-use anyhow::{Context, Result, anyhow};
-use futures::future;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::{process::exit, str::FromStr};
-use strum::{EnumIter, IntoEnumIterator};
-
-use super::{
-    deserialize::{OfferWrapper, deserialize_dealer_name, deserialize_offer},
-    offer::Offer,
-};
-use crate::OutputFormat;
-
-#[derive(
-    Hash,
-    Debug,
-    EnumIter,
-    Serialize,
-    Deserialize,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Clone,
-    Copy,
-    Default,
-)]
-pub enum Dealer {
-    Rema1000,
-    Netto,
-    DagliBrugsen,
-    SuperBrugsen,
-    Aldi,
-    Bilka,
-    Coop365,
-    Irma,
-    Føtex,
-    Lidl,
-    Meny,
-    Kvickly,
-    Spar,
-    #[default]
-    Unknown,
+
+use super::client::Client;
+use crate::error::Result;
+
+/// Dealers that can be resolved without asking the API.
+///
+/// These are the chains etb originally shipped with, so their names keep working as
+/// aliases even when the dealer list cannot be fetched.
+const BUILTIN_DEALERS: &[(&str, &str)] = &[
+    ("11deC", "Rema 1000"),
+    ("9ba51", "Netto"),
+    ("d311fg", "Dagli'Brugsen"),
+    ("0b1e8", "SuperBrugsen"),
+    ("98b7e", "Aldi"),
+    ("93f13", "Bilka"),
+    ("DWZE1w", "Coop 365"),
+    ("d432U", "Irma"),
+    ("bdf5A", "Føtex"),
+    ("71c90", "Lidl"),
+    ("267e1m", "Meny"),
+    ("c1edq", "Kvickly"),
+    ("88ddE", "Spar"),
+];
+
+/// A dealer (chain) publishing catalogs on eTilbudsavis.
+///
+/// Dealers are identified by their id alone, as chains are renamed from time to time.
+/// Sort by name explicitly where the order is shown.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "DealerRepr")]
+pub struct Dealer {
+    pub name: String,
+    pub id: String,
 }
 
-impl Dealer {
-    #[inline(always)]
-    fn id(&self) -> &'static str {
-        match self {
-            Dealer::Rema1000 => "11deC",
-            Dealer::Netto => "9ba51",
-            Dealer::DagliBrugsen => "d311fg",
-            Dealer::Aldi => "98b7e",
-            Dealer::Bilka => "93f13",
-            Dealer::Coop365 => "DWZE1w",
-            Dealer::Irma => "d432U",
-            Dealer::Føtex => "bdf5A",
-            Dealer::Lidl => "71c90",
-            Dealer::Meny => "267e1m",
-            Dealer::Kvickly => "c1edq",
-            Dealer::Spar => "88ddE",
-            Dealer::SuperBrugsen => "0b1e8",
-            Dealer::Unknown => "???",
+/// Dealers used to be stored by their built-in name, e.g. `"Netto"`, which still works
+/// for the built-in dealers. Any other dealer needs its id.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DealerRepr {
+    Name(String),
+    Full { id: String, name: String },
+}
+
+impl TryFrom<DealerRepr> for Dealer {
+    type Error = String;
+
+    fn try_from(repr: DealerRepr) -> Result<Self, Self::Error> {
+        match repr {
+            DealerRepr::Full { id, name } => Ok(Dealer { name, id }),
+            DealerRepr::Name(name) => Dealer::resolve(&name, &[]).ok_or_else(|| {
+                format!(
+                    "unknown dealer \"{name}\", only built-in dealers can be given by name. \
+                         Give other dealers with the id shown by `etb dealers`, \
+                         e.g. {{ name = \"{name}\", id = \"<id>\" }}"
+                )
+            }),
         }
     }
+}
 
-    pub(crate) fn list_known_dealers(format: Option<OutputFormat>) {
-        let format = format.unwrap_or(OutputFormat::Table);
-        let dealers: Vec<_> = Dealer::iter()
-            .filter(|&dealer| dealer != Dealer::Unknown)
-            .collect();
-
-        match format {
-            OutputFormat::Json => {
-                println!(
-                    "{}",
-                    serde_json::to_string(&dealers)
-                        .unwrap_or("Failed to Serialize dealers".to_string())
-                );
-            }
-            OutputFormat::Rss => {
-                eprintln!("Unsupported output format for dealers");
-                exit(1);
-            }
-            OutputFormat::Table => {
-                let mut table = comfy_table::Table::new();
-
-                table
-                    .load_preset(comfy_table::presets::UTF8_FULL)
-                    .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
-                    .set_header(vec!["Dealers"]);
-
-                for dealer in dealers {
-                    table.add_row(vec![dealer.to_string()]);
-                }
-
-                println!("{table}");
-            }
+impl Dealer {
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+        Dealer {
+            name: name.into(),
+            id: id.into(),
         }
     }
 
-    pub(crate) async fn remote_offers_for_dealer(&self) -> Vec<Offer> {
-        let client = Client::new();
-        let catalogs = retrieve_catalogs_from_dealer(self, &client)
-            .await
-            .unwrap_or_default();
-        let tasks: Vec<_> = catalogs
-            .into_iter()
-            .map(|catalog| {
-                let catalog = catalog.clone();
-                let client = client.clone();
-                tokio::spawn(async move { retrieve_offers_from_catalog(catalog, &client).await })
-            })
-            .collect();
-
-        future::join_all(tasks)
-            .await
-            .into_iter()
-            .flatten()
-            .flatten()
-            .flatten()
+    /// Dealers known without asking the API.
+    pub fn builtin() -> Vec<Dealer> {
+        BUILTIN_DEALERS
+            .iter()
+            .map(|&(id, name)| Dealer::new(id, name))
             .collect()
     }
+
+    /// Lowercase name without spaces or punctuation, e.g. `rema1000` or `daglibrugsen`.
+    pub fn slug(&self) -> String {
+        slugify(&self.name)
+    }
+
+    /// Find the dealer referred to by `query`, which can be an id, a name or a slug.
+    ///
+    /// `directory` is searched before the built-in dealers, so a renamed dealer is
+    /// reported by its current name.
+    pub fn resolve(query: &str, directory: &[Dealer]) -> Option<Dealer> {
+        let query = query.trim();
+        let slug = slugify(query);
+        let builtin = Dealer::builtin();
+
+        let by_id = |dealers: &[Dealer]| dealers.iter().find(|dealer| dealer.id == query).cloned();
+        let by_slug =
+            |dealers: &[Dealer]| dealers.iter().find(|dealer| dealer.slug() == slug).cloned();
+
+        by_id(directory).or_else(|| by_slug(directory)).or_else(|| {
+            let dealer = by_id(&builtin).or_else(|| by_slug(&builtin))?;
+            Some(
+                directory
+                    .iter()
+                    .find(|known| known.id == dealer.id)
+                    .cloned()
+                    .unwrap_or(dealer),
+            )
+        })
+    }
+
+    /// Fetch every dealer known to the API.
+    pub async fn retrieve_all(client: &Client) -> Result<Vec<Dealer>> {
+        Ok(client.get_paginated("/v2/dealers", &[]).await?.items)
+    }
 }
 
-impl std::fmt::Display for Dealer {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+impl PartialEq for Dealer {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
     }
 }
 
-impl FromStr for Dealer {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let value = match s.to_lowercase().trim() {
-            "bilka" => Dealer::Bilka,
-            "coop365" => Dealer::Coop365,
-            "lidl" => Dealer::Lidl,
-            "rema1000" | "rema 1000" => Dealer::Rema1000,
-            "spar" => Dealer::Spar,
-            "meny" => Dealer::Meny,
-            "føtex" => Dealer::Føtex,
-            "irma" => Dealer::Irma,
-            "aldi" => Dealer::Aldi,
-            "netto" => Dealer::Netto,
-            "kvickly" => Dealer::Kvickly,
-            "daglibrugsen" | "dagli'brugsen" => Dealer::DagliBrugsen,
-            "superbrugsen" => Dealer::SuperBrugsen,
-            _ => {
-                return Err(anyhow!(
-                    "Unknown dealer: {s}.\nSee `dealers` for available dealers."
-                ));
-            }
-        };
-        Ok(value)
+impl Eq for Dealer {}
+
+impl std::hash::Hash for Dealer {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
     }
 }
 
-#[derive(Deserialize, Clone)]
-struct Catalog {
-    id: String,
-    #[serde(deserialize_with = "deserialize_dealer_name")]
-    dealer: String,
+impl PartialOrd for Dealer {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-async fn retrieve_catalogs_from_dealer(dealer: &Dealer, client: &Client) -> Result<Vec<Catalog>> {
-    let catalog_response = client
-        .get("https://squid-api.tjek.com/v2/catalogs")
-        .query(&[("dealer_ids", dealer.id())])
-        .header("Accept", "application/json")
-        .send()
-        .await?;
-    match catalog_response.status() {
-        reqwest::StatusCode::OK => catalog_response
-            .json::<Vec<Catalog>>()
-            .await
-            .context("Dealer returned invalid JSON"),
-        _ => {
-            println!(
-                "Did not succesfully access API, StatusCode: {}",
-                catalog_response.status()
-            );
-            Ok(Vec::new())
-        }
+impl Ord for Dealer {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl std::fmt::Display for Dealer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
     }
 }
 
-async fn retrieve_offers_from_catalog(catalog: Catalog, client: &Client) -> Result<Vec<Offer>> {
-    let offers = client
-        .get(format!(
-            "https://squid-api.tjek.com/v2/catalogs/{}/hotspots",
-            catalog.id.as_str()
-        ))
-        .send()
-        .await?
-        .json::<Vec<OfferWrapper>>()
-        .await?
-        .into_iter()
-        .map(|ow| deserialize_offer(ow, &catalog.dealer))
-        .collect();
-    Ok(offers)
+fn slugify(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
 }
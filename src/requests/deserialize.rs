@@ -1,96 +1,505 @@
-use std::str::FromStr;
-
-use crate::Offer;
+use super::{
+    catalog::Catalog,
+    category::Category,
+    dealer::Dealer,
+    location::Location,
+    offer::{CostRange, Images, MalformedOffer, Offer, RetrievedOffers},
+    store::{OpeningHours, Store},
+    unit::Dimension,
+};
+use chrono::{NaiveDate, NaiveTime};
 use serde::Deserialize;
+use serde_json::Value;
 
-pub fn deserialize_dealer_name<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: serde::de::Deserializer<'de>,
-{
-    #[derive(Deserialize)]
-    struct Outer {
-        name: String,
-    }
-
-    let helper = Outer::deserialize(deserializer)?;
-    Ok(helper.name)
+#[derive(Deserialize)]
+pub struct OfferWrapper {
+    offer: Option<Outer>,
+    page: Option<u32>,
 }
 
+/// Offer as returned by the offer search endpoint, which is not wrapped in a hotspot.
 #[derive(Deserialize)]
-pub struct OfferWrapper {
+pub struct SearchedOffer {
+    #[serde(flatten)]
     offer: Outer,
+    dealer_id: Option<String>,
+    dealer: Option<Dealer>,
+    catalog_id: Option<String>,
+    catalog_page: Option<u32>,
 }
+
+// Every field is optional, as the API leaves out or nulls whatever does not apply to
+// an offer. Missing fields are only rejected when converting into an `Offer`.
 #[derive(Deserialize)]
 struct Outer {
-    id: String,
+    id: Option<String>,
     #[serde(rename = "heading")]
-    name: String,
-    pricing: Pricing,
-    run_from: String,
-    run_till: String,
-    quantity: Quantity,
+    name: Option<String>,
+    pricing: Option<Pricing>,
+    run_from: Option<String>,
+    run_till: Option<String>,
+    quantity: Option<Quantity>,
+    description: Option<String>,
+    images: Option<Images>,
+    links: Option<Links>,
+    categories: Option<Vec<ApiCategory>>,
 }
 
 #[derive(Deserialize)]
-struct Pricing {
-    price: f64,
+struct ApiCategory {
+    id: Option<String>,
+    name: Option<String>,
 }
 
 #[derive(Deserialize)]
-struct Quantity {
-    unit: Unit,
-    pieces: Pieces,
-    size: Size,
+struct Links {
+    webshop: Option<String>,
 }
 
 #[derive(Deserialize)]
+struct Pricing {
+    price: Option<f64>,
+    pre_price: Option<f64>,
+    currency: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Quantity {
+    unit: Option<Unit>,
+    pieces: Option<Pieces>,
+    size: Option<Size>,
+}
+
+#[derive(Deserialize, Default)]
 struct Size {
-    from: f64,
-    to: f64,
+    from: Option<f64>,
+    to: Option<f64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct Pieces {
-    from: u32,
-    to: u32,
+    from: Option<u32>,
+    to: Option<u32>,
 }
 
 #[derive(Deserialize)]
 struct Unit {
-    si: SI,
+    symbol: Option<String>,
+    si: Option<SI>,
 }
 
 #[derive(Deserialize)]
 struct SI {
-    symbol: String,
-    factor: f64,
-}
-
-pub(crate) fn deserialize_offer(offer_wrapper: OfferWrapper, dealer_name: &str) -> Offer {
-    let offer = &offer_wrapper.offer;
-    let factor = &offer.quantity.unit.si.factor;
-    let pieces = &offer.quantity.pieces;
-    let size = &offer.quantity.size;
-    Offer {
-        id: offer.id.to_owned(),
-        name: offer.name.to_owned(),
-        price: offer.pricing.price,
-        min_amount: pieces.from,
-        max_amount: pieces.to,
-        min_size: size.from * factor,
-        max_size: size.to * factor,
-        unit: offer.quantity.unit.si.symbol.to_owned(),
-        cost_per_unit: offer.pricing.price / (size.to * factor) / pieces.to as f64,
-        dealer: super::dealer::Dealer::from_str(dealer_name).unwrap_or_default(),
-        run_from: chrono::NaiveDate::parse_from_str(
-            offer.run_from.split('T').next().unwrap(),
-            "%Y-%m-%d",
-        )
-        .expect("failed to format NaiveDate from API date"),
-        run_till: chrono::NaiveDate::parse_from_str(
-            offer.run_till.split('T').next().unwrap(),
-            "%Y-%m-%d",
-        )
-        .expect("failed to format NaiveDate from API date"),
+    symbol: Option<String>,
+    factor: Option<f64>,
+}
+
+/// Where in a catalog an offer was found.
+struct CatalogPage<'a> {
+    catalog_id: Option<&'a str>,
+    page: Option<u32>,
+}
+
+fn deserialize_offer(offer: Outer, dealer: &Dealer, catalog: CatalogPage) -> Result<Offer, String> {
+    let id = offer.id.ok_or("missing id")?;
+    let name = offer.name.ok_or("missing heading")?;
+    let pricing = offer.pricing.ok_or("missing price")?;
+    let price = pricing.price.ok_or("missing price")?;
+    let currency = pricing.currency.unwrap_or_else(|| "DKK".to_string());
+    // Only a regular price above the offer price is a saving worth reporting.
+    let pre_price = pricing.pre_price.filter(|pre_price| *pre_price > 0.0);
+    let savings = pre_price
+        .map(|pre_price| pre_price - price)
+        .filter(|savings| *savings > 0.0);
+    let discount = savings
+        .zip(pre_price)
+        .map(|(savings, pre_price)| savings / pre_price * 100.0);
+    let run_from = parse_date(offer.run_from.as_deref()).ok_or("invalid run_from")?;
+    let run_till = parse_date(offer.run_till.as_deref()).ok_or("invalid run_till")?;
+
+    let quantity = offer.quantity.unwrap_or_default();
+    let pieces = quantity.pieces.unwrap_or_default();
+    let min_amount = pieces.from.or(pieces.to).unwrap_or(1).max(1);
+    let max_amount = pieces.to.or(pieces.from).unwrap_or(1).max(min_amount);
+    // The price of a multi-buy offer covers every item, but the API does not always
+    // count them in the pieces.
+    let multi_buy = detect_multi_buy(&name, offer.description.as_deref());
+    let (min_amount, max_amount) = match multi_buy {
+        Some(count) if max_amount == 1 => (count, count),
+        _ => (min_amount, max_amount),
+    };
+
+    // Non-food items commonly have no unit, in which case there is no unit price.
+    let (unit, factor) = match quantity.unit {
+        Some(Unit { symbol, si }) => {
+            let (si_symbol, si_factor) = match si {
+                Some(si) => (si.symbol, si.factor),
+                None => (None, None),
+            };
+            match Dimension::from_api(symbol.as_deref(), si_symbol.as_deref(), si_factor) {
+                Some((dimension, factor)) => (Some(dimension), factor),
+                None => (None, 1.0),
+            }
+        }
+        None => (None, 1.0),
+    };
+    let size = quantity.size.unwrap_or_default();
+    let min_size = size.from.or(size.to).unwrap_or(0.0) * factor;
+    // A range like `500-0` has no upper bound worth dividing by.
+    let max_size = (size.to.or(size.from).unwrap_or(0.0) * factor).max(min_size);
+    // The most you can get for the price gives the lowest unit price, and the least
+    // gives the highest.
+    let cost_per_unit = (unit.is_some() && min_size > 0.0)
+        .then(|| CostRange {
+            min: price / (max_size * max_amount as f64),
+            max: price / (min_size * min_amount as f64),
+        })
+        .filter(|cost| cost.min.is_finite() && cost.max.is_finite());
+
+    let mut categories: Vec<_> = offer
+        .categories
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|category| {
+            let id = category.id.as_deref().and_then(Category::from_api);
+            id.or_else(|| category.name.as_deref().and_then(Category::from_api))
+        })
+        .collect();
+    categories.sort();
+    categories.dedup();
+    if categories.is_empty() {
+        categories.push(Category::classify(&name, offer.description.as_deref()));
+    }
+
+    Ok(Offer {
+        id,
+        name,
+        price,
+        pre_price,
+        currency,
+        savings,
+        discount,
+        min_amount,
+        max_amount,
+        min_size,
+        max_size,
+        unit,
+        cost_per_unit,
+        multi_buy,
+        dealer: dealer.clone(),
+        run_from,
+        run_till,
+        description: offer
+            .description
+            .map(|description| description.trim().to_string())
+            .filter(|description| !description.is_empty()),
+        images: offer.images.unwrap_or_default(),
+        webshop: offer
+            .links
+            .and_then(|links| links.webshop)
+            .filter(|webshop| !webshop.is_empty()),
+        categories,
+        catalog_id: catalog.catalog_id.map(str::to_string),
+        catalog_page: catalog.page,
+    })
+}
+
+/// Words that may come between the count and "for" in a multi-buy offer, as in
+/// "3 stk. for 50 kr".
+const MULTI_BUY_ITEMS: &[&str] = &[
+    "stk", "stk.", "pk", "pk.", "pakker", "poser", "bakker", "flasker", "dåser", "ruller",
+];
+
+/// Find a multi-buy offer such as "3 for 50 kr" or "2 stk. 30,-" in the heading or
+/// description, returning how many items must be bought.
+fn detect_multi_buy(heading: &str, description: Option<&str>) -> Option<u32> {
+    [Some(heading), description]
+        .into_iter()
+        .flatten()
+        .find_map(|text| {
+            let words: Vec<_> = text.split_whitespace().map(str::to_lowercase).collect();
+            words.iter().enumerate().find_map(|(i, word)| {
+                let count = word.parse::<u32>().ok().filter(|count| *count >= 2)?;
+                let rest = &words[i + 1..];
+                match rest.first() {
+                    // The price may follow the items directly, as in "2 stk. 30,-".
+                    Some(item) if MULTI_BUY_ITEMS.contains(&item.as_str()) => {
+                        let rest = &rest[1..];
+                        (rest.first()? == "for" || is_price(rest)).then_some(count)
+                    }
+                    Some(word) => (word == "for").then_some(count),
+                    None => None,
+                }
+            })
+        })
+}
+
+/// Whether `words` start with a price, e.g. "30,-" or "30 kr".
+fn is_price(words: &[String]) -> bool {
+    let Some(amount) = words.first() else {
+        return false;
+    };
+    let is_number =
+        |amount: &str| !amount.is_empty() && amount.replace(',', ".").parse::<f64>().is_ok();
+    match amount
+        .strip_suffix(",-")
+        .or_else(|| amount.strip_suffix(".-"))
+    {
+        Some(amount) => is_number(amount),
+        None => {
+            is_number(amount)
+                && words
+                    .get(1)
+                    .is_some_and(|unit| matches!(unit.as_str(), "kr" | "kr." | "kr,-"))
+        }
+    }
+}
+
+/// Parse the date part of an API timestamp, e.g. `2026-10-11T00:00:00+0000`.
+fn parse_date(timestamp: Option<&str>) -> Option<NaiveDate> {
+    let date = timestamp?.split('T').next()?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+fn malformed(value: &Value, catalog_id: Option<&str>, reason: String) -> MalformedOffer {
+    let offer = value.get("offer").unwrap_or(value);
+    MalformedOffer {
+        offer_id: offer["id"].as_str().map(str::to_string),
+        catalog_id: catalog_id.map(str::to_string),
+        reason,
+    }
+}
+
+/// Convert the hotspots of a catalog, skipping offers that cannot be converted.
+pub(crate) fn deserialize_hotspots(
+    hotspots: Vec<Value>,
+    dealer: &Dealer,
+    catalog_id: &str,
+) -> RetrievedOffers {
+    let mut retrieved = RetrievedOffers::default();
+    for value in hotspots {
+        let offer = serde_json::from_value::<OfferWrapper>(value.clone())
+            .map_err(|err| err.to_string())
+            .and_then(|wrapper| {
+                let catalog = CatalogPage {
+                    catalog_id: Some(catalog_id),
+                    page: wrapper.page,
+                };
+                let offer = wrapper
+                    .offer
+                    .ok_or_else(|| "hotspot has no offer".to_string())?;
+                deserialize_offer(offer, dealer, catalog)
+            });
+        match offer {
+            Ok(offer) => retrieved.offers.push(offer),
+            Err(reason) => retrieved
+                .malformed
+                .push(malformed(&value, Some(catalog_id), reason)),
+        }
+    }
+    retrieved
+}
+
+/// Convert searched offers, looking up their dealer in `directory` if the API did not
+/// embed it, and skipping offers that cannot be converted.
+pub(crate) fn deserialize_searched_offers(
+    searched: Vec<Value>,
+    directory: &[Dealer],
+) -> RetrievedOffers {
+    let mut retrieved = RetrievedOffers::default();
+    for value in searched {
+        let offer = serde_json::from_value::<SearchedOffer>(value.clone())
+            .map_err(|err| err.to_string())
+            .and_then(|searched| {
+                let dealer = match (searched.dealer, searched.dealer_id) {
+                    (Some(dealer), _) => dealer,
+                    (None, Some(id)) => {
+                        Dealer::resolve(&id, directory).unwrap_or_else(|| Dealer::new(&id, &id))
+                    }
+                    (None, None) => return Err("missing dealer".to_string()),
+                };
+                let catalog = CatalogPage {
+                    catalog_id: searched.catalog_id.as_deref(),
+                    page: searched.catalog_page,
+                };
+                deserialize_offer(searched.offer, &dealer, catalog)
+            });
+        match offer {
+            Ok(offer) => retrieved.offers.push(offer),
+            Err(reason) => {
+                let catalog_id = value["catalog_id"].as_str();
+                retrieved
+                    .malformed
+                    .push(malformed(&value, catalog_id, reason));
+            }
+        }
+    }
+    retrieved
+}
+
+#[derive(Deserialize)]
+pub struct RawCatalog {
+    id: String,
+    label: Option<String>,
+    run_from: Option<String>,
+    run_till: Option<String>,
+}
+
+pub(crate) fn deserialize_catalog(raw: RawCatalog, dealer: &Dealer) -> Catalog {
+    Catalog {
+        id: raw.id,
+        dealer: dealer.clone(),
+        label: raw.label.filter(|label| !label.is_empty()),
+        run_from: parse_date(raw.run_from.as_deref()),
+        run_till: parse_date(raw.run_till.as_deref()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RawStore {
+    id: String,
+    #[serde(default)]
+    street: String,
+    #[serde(default)]
+    zip_code: String,
+    #[serde(default)]
+    city: String,
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    opening_hours: Vec<RawOpeningHours>,
+}
+
+#[derive(Deserialize)]
+struct RawOpeningHours {
+    day_of_week: Option<String>,
+    opens: Option<String>,
+    closes: Option<String>,
+}
+
+pub(crate) fn deserialize_store(raw: RawStore, dealer: &Dealer, location: &Location) -> Store {
+    let parse_time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M:%S").ok();
+    // Special opening hours (e.g. holidays) have no weekday and are left out.
+    let opening_hours = raw
+        .opening_hours
+        .iter()
+        .filter_map(|hours| {
+            Some(OpeningHours {
+                day: hours.day_of_week.as_deref()?.parse().ok()?,
+                opens: parse_time(hours.opens.as_deref()?)?,
+                closes: parse_time(hours.closes.as_deref()?)?,
+            })
+        })
+        .collect();
+
+    Store {
+        distance: location.distance_to(raw.latitude, raw.longitude),
+        id: raw.id,
+        dealer: dealer.clone(),
+        street: raw.street,
+        zip_code: raw.zip_code,
+        city: raw.city,
+        latitude: raw.latitude,
+        longitude: raw.longitude,
+        opening_hours,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn offer(value: Value) -> Offer {
+        let outer: Outer = serde_json::from_value(value).unwrap();
+        let catalog = CatalogPage {
+            catalog_id: Some("c1"),
+            page: None,
+        };
+        deserialize_offer(outer, &Dealer::new("9ba51", "Netto"), catalog).unwrap()
+    }
+
+    /// An offer of coffee at `price`, with the given quantity.
+    fn coffee(heading: &str, price: f64, quantity: Value) -> Offer {
+        offer(json!({
+            "id": "x1",
+            "heading": heading,
+            "pricing": {"price": price},
+            "quantity": quantity,
+            "run_from": "2026-10-11T00:00:00+0000",
+            "run_till": "2026-10-24T23:59:59+0000"
+        }))
+    }
+
+    fn grams(from: f64, to: f64) -> Value {
+        json!({"symbol": "g", "si": {"symbol": "kg", "factor": 0.001}, "from": from, "to": to})
+    }
+
+    fn quantity(size: Value, pieces: Option<(u32, u32)>) -> Value {
+        let mut quantity = json!({
+            "unit": {"symbol": size["symbol"], "si": size["si"]},
+            "size": {"from": size["from"], "to": size["to"]}
+        });
+        if let Some((from, to)) = pieces {
+            quantity["pieces"] = json!({"from": from, "to": to});
+        }
+        quantity
+    }
+
+    fn cost(offer: &Offer) -> (f64, f64) {
+        let cost = offer.cost_per_unit.unwrap();
+        (cost.min, cost.max)
+    }
+
+    #[test]
+    fn detects_multi_buy() {
+        assert_eq!(detect_multi_buy("Kaffe 3 for 50", None), Some(3));
+        assert_eq!(detect_multi_buy("Kaffe", Some("3 stk. for 50 kr")), Some(3));
+        assert_eq!(detect_multi_buy("Skyr", Some("2 stk. 30,-")), Some(2));
+        assert_eq!(detect_multi_buy("Øl", Some("6 dåser 100 kr")), Some(6));
+    }
+
+    #[test]
+    fn ignores_text_without_multi_buy() {
+        assert_eq!(detect_multi_buy("Kaffe", None), None);
+        assert_eq!(detect_multi_buy("Kaffe", Some("500 g. Frit valg")), None);
+        assert_eq!(detect_multi_buy("Æg", Some("10 stk. Skrabeæg")), None);
+        assert_eq!(detect_multi_buy("Pizza", Some("2 stk. 350 g")), None);
+        // A single item for a price is not a multi-buy.
+        assert_eq!(detect_multi_buy("Kaffe 1 for 25", None), None);
+    }
+
+    #[test]
+    fn prices_multi_buy_per_item() {
+        let offer = coffee("Kaffe 3 for 90", 90.0, quantity(grams(500.0, 500.0), None));
+        assert_eq!(offer.multi_buy, Some(3));
+        assert_eq!((offer.min_amount, offer.max_amount), (3, 3));
+        assert_eq!(cost(&offer), (60.0, 60.0));
+    }
+
+    #[test]
+    fn prices_size_and_amount_ranges() {
+        let offer = coffee("Kaffe", 60.0, quantity(grams(400.0, 500.0), Some((1, 2))));
+        assert!(offer.cost_per_unit.unwrap().is_range());
+        // Two bags of 500 g is the most, one bag of 400 g the least for the price.
+        assert_eq!(cost(&offer), (60.0, 150.0));
+    }
+
+    #[test]
+    fn prices_an_empty_size_range_by_its_lower_bound() {
+        let offer = coffee("Kaffe", 45.0, quantity(grams(500.0, 0.0), None));
+        assert_eq!((offer.min_size, offer.max_size), (0.5, 0.5));
+        assert_eq!(cost(&offer), (90.0, 90.0));
+
+        // Offers must survive a round trip through the cache file.
+        let json = serde_json::to_string(&offer).unwrap();
+        let read: Offer = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.cost_per_unit, offer.cost_per_unit);
+    }
+
+    #[test]
+    fn has_no_unit_price_without_a_size() {
+        let offer = coffee("Kaffe", 45.0, quantity(grams(0.0, 0.0), None));
+        assert_eq!(offer.cost_per_unit, None);
     }
 }
@@ -0,0 +1,169 @@
+use chrono::NaiveDate;
+use regex::Regex;
+
+use super::{
+    category::Category,
+    dealer::Dealer,
+    matcher::{NameMatcher, normalize},
+    offer::Offer,
+    unit::{Dimension, UnitPrice},
+};
+
+/// A condition offers must meet to be shown, composed from smaller conditions with
+/// [`OfferFilter::and`], [`OfferFilter::or`] and [`OfferFilter::negate`].
+#[derive(Debug, Clone)]
+pub enum OfferFilter {
+    /// Matches every offer.
+    All,
+    /// The name matches a search term.
+    Name(NameMatcher),
+    /// The name contains this text, ignoring case, Danish letters and accents but not
+    /// typos.
+    NameContains(String),
+    /// The name matches a regular expression.
+    NamePattern(Regex),
+    /// Offered by one of the dealers.
+    Dealers(Vec<Dealer>),
+    /// Offered by a dealer with this id, or whose name starts with this.
+    DealerName(String),
+    Price(Comparison, f64),
+    /// The lowest unit price compared to this, in the same unit.
+    UnitPrice(Comparison, UnitPrice),
+    /// Discount in percent.
+    Discount(Comparison, f64),
+    /// Measured in this unit.
+    Unit(Dimension),
+    /// Valid on the date.
+    ValidOn(NaiveDate),
+    Category(Category),
+    And(Vec<OfferFilter>),
+    Or(Vec<OfferFilter>),
+    Not(Box<OfferFilter>),
+}
+
+/// How a value is compared to a bound, as in `price<20`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    AtMost,
+    Equal,
+    AtLeast,
+    Greater,
+}
+
+impl Comparison {
+    pub fn holds(self, value: f64, bound: f64) -> bool {
+        match self {
+            Comparison::Less => value < bound,
+            Comparison::AtMost => value <= bound,
+            // Prices are given in øre at most.
+            Comparison::Equal => (value - bound).abs() < 0.005,
+            Comparison::AtLeast => value >= bound,
+            Comparison::Greater => value > bound,
+        }
+    }
+}
+
+impl OfferFilter {
+    pub fn matches(&self, offer: &Offer) -> bool {
+        match self {
+            OfferFilter::All => true,
+            OfferFilter::Name(matcher) => matcher.matches(&offer.name),
+            OfferFilter::NameContains(text) => normalize(&offer.name).contains(&normalize(text)),
+            OfferFilter::NamePattern(pattern) => pattern.is_match(&offer.name),
+            OfferFilter::Dealers(dealers) => dealers.contains(&offer.dealer),
+            OfferFilter::DealerName(name) => {
+                offer.dealer.id == *name || compact(&offer.dealer.name).starts_with(&compact(name))
+            }
+            OfferFilter::Price(comparison, bound) => comparison.holds(offer.price, *bound),
+            OfferFilter::UnitPrice(comparison, bound) => offer
+                .unit
+                .zip(offer.cost_per_unit)
+                .is_some_and(|(unit, cost)| {
+                    unit == bound.dimension && comparison.holds(cost.min, bound.amount)
+                }),
+            OfferFilter::Discount(comparison, bound) => offer
+                .discount
+                .is_some_and(|discount| comparison.holds(discount, *bound)),
+            OfferFilter::Unit(dimension) => offer.unit == Some(*dimension),
+            OfferFilter::ValidOn(date) => (offer.run_from..=offer.run_till).contains(date),
+            OfferFilter::Category(category) => offer.categories.contains(category),
+            OfferFilter::And(filters) => filters.iter().all(|filter| filter.matches(offer)),
+            OfferFilter::Or(filters) => filters.iter().any(|filter| filter.matches(offer)),
+            OfferFilter::Not(filter) => !filter.matches(offer),
+        }
+    }
+
+    /// Match offers matching both filters.
+    pub fn and(self, other: OfferFilter) -> OfferFilter {
+        match (self, other) {
+            (OfferFilter::All, filter) | (filter, OfferFilter::All) => filter,
+            (OfferFilter::And(mut filters), OfferFilter::And(others)) => {
+                filters.extend(others);
+                OfferFilter::And(filters)
+            }
+            (OfferFilter::And(mut filters), filter) => {
+                filters.push(filter);
+                OfferFilter::And(filters)
+            }
+            (filter, other) => OfferFilter::And(vec![filter, other]),
+        }
+    }
+
+    /// Match offers matching either filter.
+    pub fn or(self, other: OfferFilter) -> OfferFilter {
+        match (self, other) {
+            (OfferFilter::All, _) | (_, OfferFilter::All) => OfferFilter::All,
+            (OfferFilter::Or(mut filters), OfferFilter::Or(others)) => {
+                filters.extend(others);
+                OfferFilter::Or(filters)
+            }
+            (OfferFilter::Or(mut filters), filter) => {
+                filters.push(filter);
+                OfferFilter::Or(filters)
+            }
+            (filter, other) => OfferFilter::Or(vec![filter, other]),
+        }
+    }
+
+    /// Match offers not matching this filter.
+    pub fn negate(self) -> OfferFilter {
+        match self {
+            OfferFilter::Not(filter) => *filter,
+            filter => OfferFilter::Not(Box::new(filter)),
+        }
+    }
+
+    /// How well an offer matches the search terms, for ranking offers. Higher is better.
+    pub fn relevance(&self, offer: &Offer) -> f64 {
+        match self {
+            OfferFilter::Name(matcher) => matcher.score(&offer.name).unwrap_or(0.0),
+            OfferFilter::NamePattern(pattern) if pattern.is_match(&offer.name) => 1.0,
+            OfferFilter::And(filters) => filters.iter().map(|filter| filter.relevance(offer)).sum(),
+            OfferFilter::Or(filters) => filters
+                .iter()
+                .map(|filter| filter.relevance(offer))
+                .fold(0.0, f64::max),
+            _ => 0.0,
+        }
+    }
+
+    /// Names that offers are searched for, leaving out names that are excluded.
+    pub fn search_terms(&self) -> Vec<&str> {
+        match self {
+            OfferFilter::Name(matcher) => vec![matcher.query()],
+            OfferFilter::And(filters) | OfferFilter::Or(filters) => {
+                filters.iter().flat_map(OfferFilter::search_terms).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Normalized name without spaces or punctuation, so `rema` matches "REMA 1000".
+fn compact(name: &str) -> String {
+    normalize(name)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
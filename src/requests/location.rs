@@ -0,0 +1,177 @@
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Danish postal districts, sorted by postcode.
+const POSTCODES: &str = include_str!("postcodes.tsv");
+
+/// Radius used when none is given, in meters.
+pub const DEFAULT_RADIUS: u32 = 10_000;
+
+/// Area to retrieve catalogs and offers for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Radius around the coordinates, in meters.
+    pub radius: u32,
+}
+
+impl Location {
+    pub fn new(latitude: f64, longitude: f64, radius: u32) -> Self {
+        Location {
+            latitude,
+            longitude,
+            radius,
+        }
+    }
+
+    /// Location of a Danish postal district, resolved from a bundled table.
+    ///
+    /// Postcodes missing from the table resolve to the nearest lower district in the
+    /// same region, as Danish postcodes are assigned geographically.
+    pub fn from_postcode(postcode: u16, radius: u32) -> Option<Location> {
+        let (_, _, latitude, longitude) = postal_districts()
+            .take_while(|&(code, ..)| code <= postcode)
+            .last()
+            .filter(|&(code, ..)| code / 1000 == postcode / 1000)?;
+        Some(Location::new(latitude, longitude, radius))
+    }
+
+    /// Name of the postal district closest to this location.
+    pub fn district(&self) -> Option<&'static str> {
+        postal_districts()
+            .min_by(|a, b| {
+                self.distance_to(a.2, a.3)
+                    .total_cmp(&self.distance_to(b.2, b.3))
+            })
+            .map(|(_, name, ..)| name)
+    }
+
+    /// Great-circle distance to the given coordinates, in meters.
+    pub fn distance_to(&self, latitude: f64, longitude: f64) -> f64 {
+        const EARTH_RADIUS: f64 = 6_371_000.0;
+        let (lat1, lat2) = (self.latitude.to_radians(), latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    /// Query parameters narrowing API requests to this location.
+    pub(crate) fn query(&self) -> [(&'static str, String); 3] {
+        [
+            ("r_lat", self.latitude.to_string()),
+            ("r_lng", self.longitude.to_string()),
+            ("r_radius", self.radius.to_string()),
+        ]
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.4}, {:.4} ({} km)",
+            self.latitude,
+            self.longitude,
+            self.radius as f64 / 1000.0
+        )?;
+        if let Some(district) = self.district() {
+            write!(f, " near {district}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses either a postcode, e.g. `8000`, or coordinates, e.g. `56.15,10.21`, using the
+/// default radius.
+impl FromStr for Location {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((latitude, longitude)) = s.split_once(',') {
+            let latitude: f64 = latitude.trim().parse().context("Invalid latitude")?;
+            let longitude: f64 = longitude.trim().parse().context("Invalid longitude")?;
+            // Parsing accepts `NaN` and `inf`, which fail every range check.
+            if !(-90.0..=90.0).contains(&latitude) {
+                bail!("Latitude must be between -90 and 90, got: {latitude}");
+            }
+            if !(-180.0..=180.0).contains(&longitude) {
+                bail!("Longitude must be between -180 and 180, got: {longitude}");
+            }
+            return Ok(Location::new(latitude, longitude, DEFAULT_RADIUS));
+        }
+
+        match s.parse::<u16>() {
+            Ok(postcode) => Location::from_postcode(postcode, DEFAULT_RADIUS)
+                .with_context(|| format!("Unknown postcode: {postcode}")),
+            Err(_) => bail!("Expected a postcode or coordinates like `56.15,10.21`, got: {s}"),
+        }
+    }
+}
+
+fn postal_districts() -> impl Iterator<Item = (u16, &'static str, f64, f64)> {
+    POSTCODES
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some((
+                fields.next()?.parse().ok()?,
+                fields.next()?,
+                fields.next()?.parse().ok()?,
+                fields.next()?.parse().ok()?,
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_postcodes() {
+        let aarhus = Location::from_postcode(8000, 5000).unwrap();
+        assert_eq!(aarhus.radius, 5000);
+        assert_eq!(aarhus.district(), Some("Aarhus C"));
+        // Unlisted postcodes fall back to the nearest lower district in the same region.
+        let unlisted = Location::from_postcode(8001, 5000).unwrap();
+        assert_eq!(unlisted, aarhus);
+        assert_eq!(Location::from_postcode(999, 5000), None);
+    }
+
+    #[test]
+    fn parses_postcodes_and_coordinates() {
+        let aarhus: Location = "8000".parse().unwrap();
+        assert_eq!(
+            aarhus,
+            Location::from_postcode(8000, DEFAULT_RADIUS).unwrap()
+        );
+        let coordinates: Location = " 56.15, 10.21 ".parse().unwrap();
+        assert_eq!(coordinates, Location::new(56.15, 10.21, DEFAULT_RADIUS));
+        let edge: Location = "-90,180".parse().unwrap();
+        assert_eq!(edge, Location::new(-90.0, 180.0, DEFAULT_RADIUS));
+    }
+
+    #[test]
+    fn rejects_invalid_locations() {
+        for input in [
+            "nan,nan",
+            "inf,10",
+            "56,-inf",
+            "95,10",
+            "-90.5,10",
+            "56,200",
+            "56,-180.1",
+            "56.15",
+            "56;10",
+            "lat,lon",
+            "999",
+            "aarhus",
+        ] {
+            assert!(input.parse::<Location>().is_err(), "{input}");
+        }
+    }
+}
@@ -0,0 +1,179 @@
+/// Matches offer names against a search term, ignoring case, Danish letters, accents and
+/// small typos, so `oksekod` finds "Hakket oksekød" and `kafe` finds "Kaffe".
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameMatcher {
+    query: String,
+    tokens: Vec<String>,
+}
+
+impl NameMatcher {
+    pub fn new(query: &str) -> Self {
+        let query = query.trim().to_string();
+        NameMatcher {
+            tokens: tokenize(&query),
+            query,
+        }
+    }
+
+    /// The search term as it was given.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// How well `name` matches, from 1.0 when every word of the search term is a word
+    /// of the name, down towards 0.0 for words only found with typos. `None` if any
+    /// word is not found at all.
+    pub fn score(&self, name: &str) -> Option<f64> {
+        if self.tokens.is_empty() {
+            return Some(1.0);
+        }
+        let words = tokenize(name);
+        let mut total = 0.0;
+        for token in &self.tokens {
+            total += words
+                .iter()
+                .filter_map(|word| token_score(token, word))
+                .max_by(f64::total_cmp)?;
+        }
+        Some(total / self.tokens.len() as f64)
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.score(name).is_some()
+    }
+}
+
+/// Score a word of the search term against a word of a name.
+fn token_score(token: &str, word: &str) -> Option<f64> {
+    if word == token {
+        return Some(1.0);
+    }
+    if word.starts_with(token) {
+        return Some(0.9);
+    }
+    let length = token.chars().count();
+    // Danish compounds such as "oksekød" hide words inside others.
+    if length >= 3 && word.contains(token) {
+        return Some(0.75);
+    }
+
+    let allowed = match length {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+    // Compare with the start of longer words too, so typos in a prefix are forgiven.
+    let prefix: String = word.chars().take(length).collect();
+    let distance = edit_distance(token, word).min(edit_distance(token, &prefix));
+    (distance <= allowed).then(|| 0.6 - 0.1 * distance.saturating_sub(1) as f64)
+}
+
+/// Case fold, transliterate Danish letters and strip accents, then split into words.
+fn tokenize(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Lower case ASCII version of `text`, as far as Latin letters go.
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'æ' => normalized.push_str("ae"),
+            'ø' => normalized.push('o'),
+            'å' => normalized.push('a'),
+            'ß' => normalized.push_str("ss"),
+            'œ' => normalized.push_str("oe"),
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'ā' => normalized.push('a'),
+            'ç' | 'ć' | 'č' => normalized.push('c'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ě' => normalized.push('e'),
+            'ì' | 'í' | 'î' | 'ï' | 'ī' => normalized.push('i'),
+            'ñ' | 'ń' | 'ň' => normalized.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ō' | 'ő' => normalized.push('o'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => normalized.push('u'),
+            'ý' | 'ÿ' => normalized.push('y'),
+            'š' | 'ś' => normalized.push('s'),
+            'ž' | 'ź' | 'ż' => normalized.push('z'),
+            'ł' => normalized.push('l'),
+            'ř' => normalized.push('r'),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Number of insertions, deletions, substitutions and swaps of neighbouring letters
+/// turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows suffice, as swaps look two letters back.
+    let mut before_previous = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before_previous, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("kaffe", "kaffe"), 0);
+        assert_eq!(edit_distance("kafe", "kaffe"), 1);
+        assert_eq!(edit_distance("kaffee", "kaffe"), 1);
+        assert_eq!(edit_distance("kaffa", "kaffe"), 1);
+        assert_eq!(edit_distance("", "kaffe"), 5);
+        assert_eq!(edit_distance("kaffe", ""), 5);
+    }
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one_edit() {
+        assert_eq!(edit_distance("kafef", "kaffe"), 1);
+        assert_eq!(edit_distance("akffe", "kaffe"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+    }
+
+    #[test]
+    fn edit_distance_compares_letters_not_bytes() {
+        assert_eq!(edit_distance("smør", "smor"), 1);
+        assert_eq!(edit_distance("æble", "æbel"), 1);
+    }
+
+    #[test]
+    fn matches_names_despite_case_accents_and_typos() {
+        assert!(NameMatcher::new("oksekod").matches("Hakket oksekød 8-12%"));
+        assert!(NameMatcher::new("KAFFE").matches("Kaffe, formalet"));
+        assert!(NameMatcher::new("kafe").matches("Kaffe"));
+        assert!(!NameMatcher::new("kaffe").matches("Pasta"));
+        // Short words must match exactly.
+        assert!(!NameMatcher::new("te").matches("Tre"));
+    }
+
+    #[test]
+    fn scores_exact_words_above_typos() {
+        let matcher = NameMatcher::new("kaffe");
+        let exact = matcher.score("Kaffe").unwrap();
+        let prefix = matcher.score("Kaffebønner").unwrap();
+        let typo = NameMatcher::new("kafe").score("Kaffe").unwrap();
+        assert!(exact > prefix && prefix > typo);
+    }
+}
@@ -0,0 +1,167 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Version of the cache file written by this version of etb. Files without a version
+/// are version 1.
+pub const CACHE_VERSION: u32 = 2;
+
+/// Turns the raw JSON of one version of the cache file into the next version.
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// Migrations from each version of the cache file to the next. The migration at index
+/// `i` turns version `i + 1` into version `i + 2`.
+const MIGRATIONS: &[Migration] = &[v1_to_v2];
+
+/// A persisted file that could not be read as is. The file was set aside so that it is
+/// not overwritten, and etb continued without its contents.
+#[derive(Debug)]
+pub struct LoadWarning {
+    pub path: PathBuf,
+    /// Where the file was moved to, if it could be moved.
+    pub backup: Option<PathBuf>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not read {}: {}. ",
+            self.path.display(),
+            self.reason
+        )?;
+        match &self.backup {
+            Some(backup) => write!(f, "It was backed up to {}", backup.display()),
+            None => write!(f, "It could not be backed up and will be overwritten"),
+        }
+    }
+}
+
+impl LoadWarning {
+    /// Move the file at `path` aside to `<path>.bak`, replacing an older backup.
+    pub(crate) fn back_up(path: &Path, reason: impl Into<String>) -> LoadWarning {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        LoadWarning {
+            path: path.to_path_buf(),
+            backup: std::fs::rename(path, &backup).ok().map(|_| backup),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Bring the JSON of a cache file up to [`CACHE_VERSION`].
+pub(crate) fn migrate_cache(mut value: Value) -> Result<Value, String> {
+    let version = match value.get("version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| format!("invalid version {version}"))?,
+    };
+    if version == 0 || version > CACHE_VERSION {
+        return Err(format!(
+            "written by another version of etb (version {version}, expected at most {CACHE_VERSION})"
+        ));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        value = migration(value)?;
+    }
+    Ok(value)
+}
+
+/// Version 2 moved favorites and the location to the config file, and replaced the
+/// list of offers with offers per dealer and catalog.
+fn v1_to_v2(mut value: Value) -> Result<Value, String> {
+    let object = value.as_object_mut().ok_or("not a JSON object")?;
+    for obsolete in [
+        "favorites",
+        "location",
+        "offers",
+        "offers_location",
+        "date_of_last_cache",
+        "favorites_changed",
+    ] {
+        object.remove(obsolete);
+    }
+    object.insert("version".to_string(), Value::from(2));
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates_v1_to_v2() {
+        let v1 = json!({
+            "favorites": ["Netto"],
+            "location": {"latitude": 55.68, "longitude": 12.57, "radius": 5000},
+            "offers": [],
+            "offers_location": null,
+            "date_of_last_cache": "2026-10-01",
+            "favorites_changed": false,
+            "dealers": [{"id": "9ba51", "name": "Netto"}],
+            "date_of_dealer_cache": "2026-10-01"
+        });
+        let v2 = migrate_cache(v1).unwrap();
+        assert_eq!(
+            v2,
+            json!({
+                "version": 2,
+                "dealers": [{"id": "9ba51", "name": "Netto"}],
+                "date_of_dealer_cache": "2026-10-01"
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_the_current_version() {
+        let current = json!({ "version": CACHE_VERSION, "dealers": [] });
+        assert_eq!(migrate_cache(current.clone()).unwrap(), current);
+    }
+
+    #[test]
+    fn rejects_a_newer_version() {
+        let err = migrate_cache(json!({ "version": CACHE_VERSION + 1 })).unwrap_err();
+        assert!(err.contains("another version"), "{err}");
+    }
+
+    #[test]
+    fn rejects_an_invalid_version() {
+        for version in [json!(0), json!(-1), json!("2"), json!(1.5), json!(u64::MAX)] {
+            assert!(
+                migrate_cache(json!({ "version": version })).is_err(),
+                "{version}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_v1_cache_that_is_not_an_object() {
+        assert!(migrate_cache(json!([])).is_err());
+    }
+
+    #[test]
+    fn backs_up_next_to_the_file() {
+        let dir = std::env::temp_dir().join(format!("etb-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cache.json");
+        let backup = dir.join("cache.json.bak");
+        std::fs::write(&backup, "older").unwrap();
+        std::fs::write(&path, "newer").unwrap();
+        let warning = LoadWarning::back_up(&path, "invalid JSON");
+        let backed_up = std::fs::read_to_string(&backup);
+        let still_there = path.exists();
+        let missing = LoadWarning::back_up(&dir.join("missing.json"), "gone");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(warning.backup, Some(backup));
+        assert_eq!(warning.reason, "invalid JSON");
+        assert_eq!(backed_up.unwrap(), "newer");
+        assert!(!still_there);
+        assert_eq!(missing.backup, None);
+    }
+}
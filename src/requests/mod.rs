@@ -1,4 +1,17 @@
-pub(crate) mod dealer;
+pub mod cache;
+pub mod catalog;
+pub mod category;
+pub mod client;
+pub mod config;
+pub mod dealer;
 pub(crate) mod deserialize;
-pub(crate) mod offer;
-pub(crate) mod userdata;
+pub mod filter;
+pub mod location;
+pub mod matcher;
+pub mod migration;
+pub mod offer;
+pub mod query;
+pub mod storage;
+pub mod store;
+pub mod unit;
+pub mod userdata;
@@ -1,27 +1,339 @@
 use chrono::NaiveDate;
+use clap::ValueEnum;
 use comfy_table::{Cell, CellAlignment};
 use serde::{Deserialize, Serialize};
 
-use super::dealer::Dealer;
+use super::{
+    category::Category, client::Client, dealer::Dealer, deserialize::deserialize_searched_offers,
+    filter::OfferFilter, location::Location, unit::Dimension,
+};
+use crate::error::Result;
+use std::{borrow::Borrow, cmp::Ordering, collections::HashSet};
 
 #[derive(Debug, Deserialize, Serialize, PartialOrd)]
-pub(crate) struct Offer {
+pub struct Offer {
     pub id: String,
     pub name: String,
     pub dealer: Dealer,
     pub price: f64,
-    pub cost_per_unit: f64,
-    pub unit: String,
+    /// Regular price before the offer, if the dealer states one.
+    #[serde(default)]
+    pub pre_price: Option<f64>,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Amount saved compared to the regular price.
+    #[serde(default)]
+    pub savings: Option<f64>,
+    /// Savings as a percentage of the regular price.
+    #[serde(default)]
+    pub discount: Option<f64>,
+    /// Price per canonical unit, if the offer has a unit and a size.
+    pub cost_per_unit: Option<CostRange>,
+    /// Number of items that must be bought together for the price, as in "3 for 50 kr".
+    #[serde(default)]
+    pub multi_buy: Option<u32>,
+    /// Dimension of the size, if known. Sizes are in its canonical unit.
+    #[serde(default, deserialize_with = "Dimension::deserialize_lenient")]
+    pub unit: Option<Dimension>,
     pub min_size: f64,
     pub max_size: f64,
     pub min_amount: u32,
     pub max_amount: u32,
     pub run_from: NaiveDate,
     pub run_till: NaiveDate,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub images: Images,
+    /// Link to the offer in the dealer's webshop.
+    #[serde(default)]
+    pub webshop: Option<String>,
+    /// Product categories, guessed from the name when the API gives none.
+    #[serde(default)]
+    pub categories: Vec<Category>,
+    /// Catalog the offer appears in, if known.
+    #[serde(default)]
+    pub catalog_id: Option<String>,
+    #[serde(default)]
+    pub catalog_page: Option<u32>,
 }
 
-pub fn sort_by_cost(a: &Offer, b: &Offer) -> std::cmp::Ordering {
-    a.cost_per_unit.total_cmp(&b.cost_per_unit).reverse()
+/// Pictures of an offer, in increasing resolution.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+pub struct Images {
+    pub thumb: Option<String>,
+    pub view: Option<String>,
+    pub zoom: Option<String>,
+}
+
+impl Images {
+    /// The largest available picture.
+    pub fn largest(&self) -> Option<&str> {
+        self.zoom
+            .as_deref()
+            .or(self.view.as_deref())
+            .or(self.thumb.as_deref())
+    }
+}
+
+/// Lowest and highest price per canonical unit. They differ when an offer comes in a
+/// range of sizes or amounts, e.g. "2-3 stk, 400-500 g".
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[serde(from = "CostRangeRepr")]
+pub struct CostRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl CostRange {
+    pub fn is_range(&self) -> bool {
+        self.max - self.min >= 0.005
+    }
+}
+
+/// Caches from older versions store a single unit price.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CostRangeRepr {
+    Single(f64),
+    Range { min: f64, max: f64 },
+}
+
+impl From<CostRangeRepr> for CostRange {
+    fn from(repr: CostRangeRepr) -> Self {
+        match repr {
+            CostRangeRepr::Single(cost) => CostRange {
+                min: cost,
+                max: cost,
+            },
+            CostRangeRepr::Range { min, max } => CostRange { min, max },
+        }
+    }
+}
+
+fn default_currency() -> String {
+    "DKK".to_string()
+}
+
+/// Offers retrieved from the API, along with the offers that had to be skipped.
+#[derive(Debug, Default)]
+pub struct RetrievedOffers {
+    pub offers: Vec<Offer>,
+    pub malformed: Vec<MalformedOffer>,
+    /// Number of offers left out for duplicating another offer.
+    pub duplicates: usize,
+    /// Listings cut short at [`ClientOptions::max_results`], e.g. `catalog f4b1Ce`.
+    ///
+    /// [`ClientOptions::max_results`]: super::client::ClientOptions::max_results
+    pub truncated: Vec<String>,
+}
+
+impl RetrievedOffers {
+    pub fn extend(&mut self, other: RetrievedOffers) {
+        self.offers.extend(other.offers);
+        self.malformed.extend(other.malformed);
+        self.duplicates += other.duplicates;
+        self.truncated.extend(other.truncated);
+    }
+
+    /// Leave out offers equal to an earlier offer, e.g. an offer appearing in both the
+    /// national and a regional catalog of a dealer.
+    pub fn dedup(&mut self) {
+        self.duplicates += dedup(&mut self.offers);
+    }
+}
+
+/// Leave out offers equal to an earlier offer, keeping the order, and return how many
+/// were left out.
+///
+/// Offers are equal if they share an id, or are the same product from the same dealer
+/// for the same period, which is not transitive, so every offer is compared to all
+/// kept offers. Only the ids and products of kept offers are remembered for that, so an
+/// offer left out for its id does not take others with the same product along.
+pub fn dedup<T: Borrow<Offer>>(offers: &mut Vec<T>) -> usize {
+    let before = offers.len();
+    let mut ids = HashSet::new();
+    let mut products = HashSet::new();
+    let mut kept: Vec<T> = Vec::with_capacity(before);
+    for offer in offers.drain(..) {
+        let borrowed = offer.borrow();
+        let product = (
+            borrowed.dealer.id.clone(),
+            borrowed.name.clone(),
+            borrowed.run_from,
+            borrowed.run_till,
+        );
+        if ids.contains(&borrowed.id) || products.contains(&product) {
+            continue;
+        }
+        ids.insert(borrowed.id.clone());
+        products.insert(product);
+        kept.push(offer);
+    }
+    *offers = kept;
+    before - offers.len()
+}
+
+/// An offer that was skipped because the API returned it with missing or invalid fields.
+#[derive(Debug, Clone)]
+pub struct MalformedOffer {
+    pub offer_id: Option<String>,
+    pub catalog_id: Option<String>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for MalformedOffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "offer {}",
+            self.offer_id.as_deref().unwrap_or("without id")
+        )?;
+        if let Some(catalog_id) = &self.catalog_id {
+            write!(f, " in catalog {catalog_id}")?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Something offers can be ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// Cheapest per unit first, grouped by unit.
+    UnitPrice,
+    /// Cheapest first.
+    Price,
+    /// Largest discount first.
+    Discount,
+    /// Dealer name, alphabetically.
+    Dealer,
+    /// Product name, alphabetically.
+    Name,
+    /// Earliest start first.
+    StartDate,
+    /// Ending soonest first.
+    EndDate,
+    /// Best match for the search terms first.
+    Relevance,
+}
+
+impl SortKey {
+    /// Compare two offers by this key alone, with `reverse` flipping the order of
+    /// offers that have a value. Offers without one, such as offers without a unit
+    /// price, always go last.
+    fn compare(self, a: &Offer, b: &Offer, filter: &OfferFilter, reverse: bool) -> Ordering {
+        match self {
+            SortKey::UnitPrice => {
+                // Unit prices are only comparable within a dimension, so offers are
+                // grouped by dimension first: kr/kg before kr/l before kr/stk before kr/m.
+                // Ranges are compared by their lowest unit price, the best deal possible.
+                let unit_price = |offer: &Offer| offer.unit.zip(offer.cost_per_unit);
+                compare_present(unit_price(a), unit_price(b), reverse, |a, b| {
+                    a.0.cmp(&b.0).then_with(|| a.1.min.total_cmp(&b.1.min))
+                })
+            }
+            SortKey::Price => compare_present(Some(a.price), Some(b.price), reverse, |a, b| {
+                a.total_cmp(&b)
+            }),
+            SortKey::Discount => {
+                compare_present(a.discount, b.discount, reverse, |a, b| b.total_cmp(&a))
+            }
+            SortKey::Dealer => compare_present(
+                Some(a.dealer.name.to_lowercase()),
+                Some(b.dealer.name.to_lowercase()),
+                reverse,
+                |a, b| a.cmp(&b),
+            ),
+            SortKey::Name => compare_present(
+                Some(a.name.to_lowercase()),
+                Some(b.name.to_lowercase()),
+                reverse,
+                |a, b| a.cmp(&b),
+            ),
+            SortKey::StartDate => {
+                compare_present(Some(a.run_from), Some(b.run_from), reverse, |a, b| {
+                    a.cmp(&b)
+                })
+            }
+            SortKey::EndDate => {
+                compare_present(Some(a.run_till), Some(b.run_till), reverse, |a, b| {
+                    a.cmp(&b)
+                })
+            }
+            SortKey::Relevance => compare_present(
+                Some(filter.relevance(a)),
+                Some(filter.relevance(b)),
+                reverse,
+                |a, b| b.total_cmp(&a),
+            ),
+        }
+    }
+}
+
+/// Compare values with `compare`, keeping missing values last even when reversed.
+fn compare_present<T>(
+    a: Option<T>,
+    b: Option<T>,
+    reverse: bool,
+    compare: impl FnOnce(T, T) -> Ordering,
+) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if reverse => compare(a, b).reverse(),
+        (Some(a), Some(b)) => compare(a, b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    }
+}
+
+/// Orders offers by several keys in turn, e.g. by dealer and then by unit price.
+#[derive(Debug, Clone)]
+pub struct OfferComparator {
+    keys: Vec<SortKey>,
+    reverse: bool,
+    /// What offers were searched for, to rank them by relevance.
+    search: OfferFilter,
+}
+
+impl Default for OfferComparator {
+    fn default() -> Self {
+        OfferComparator {
+            keys: Vec::new(),
+            reverse: false,
+            search: OfferFilter::All,
+        }
+    }
+}
+
+impl OfferComparator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Break ties of the previous keys with `key`.
+    pub fn then_by(mut self, key: SortKey) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// Flip the order of every key, still keeping offers without a value last.
+    pub fn reversed(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Rank offers by how well they match `search` when sorting by relevance.
+    pub fn relevance_to(mut self, search: OfferFilter) -> Self {
+        self.search = search;
+        self
+    }
+
+    pub fn compare(&self, a: &Offer, b: &Offer) -> Ordering {
+        self.keys
+            .iter()
+            .map(|key| key.compare(a, b, &self.search, self.reverse))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
 }
 
 impl PartialEq for Offer {
@@ -39,30 +351,129 @@ impl Eq for Offer {}
 impl std::fmt::Display for Offer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let offer_str = format!(
-            "{} - {}: {} - {}: {} kr. - {:.2} kr/{}",
+            "{} - {}: {} - {}: {}",
             self.run_from.format("%d/%m"),
             self.run_till.format("%d/%m"),
             self.dealer,
             self.name,
-            self.price,
-            self.cost_per_unit,
-            self.unit
+            self.format_price(),
         );
         write!(f, "{}", offer_str)?;
+        if let (Some(pre_price), Some(discount)) = (self.pre_price, self.discount) {
+            write!(
+                f,
+                " (was {}, -{:.0}%)",
+                self.format_amount(pre_price),
+                discount
+            )?;
+        }
+        if let Some(cost_per_unit) = self.format_cost_per_unit() {
+            write!(f, " - {}", cost_per_unit)?;
+        }
         Ok(())
     }
 }
 
 impl Offer {
-    pub(crate) fn to_table_entry(&self) -> Vec<Cell> {
-        let unit = &self.unit;
+    /// Search the offers of every dealer using the API's full-text search, limited to
+    /// offers valid at `location` if given.
+    ///
+    /// `directory` is used to name dealers the API only refers to by id.
+    pub async fn search_remote(
+        client: &Client,
+        search: &str,
+        directory: &[Dealer],
+        location: Option<&Location>,
+    ) -> Result<RetrievedOffers> {
+        let location = location.map(Location::query);
+        let mut query = vec![("query", search)];
+        query.extend(
+            location
+                .iter()
+                .flatten()
+                .map(|(key, value)| (*key, value.as_str())),
+        );
+        let searched = client.get_paginated("/v2/offers/search", &query).await?;
+        let mut retrieved = deserialize_searched_offers(searched.items, directory);
+        if searched.truncated {
+            retrieved.truncated.push(format!("the search for {search}"));
+        }
+        Ok(retrieved)
+    }
+
+    /// Format an amount in the currency of the offer.
+    pub fn format_amount(&self, amount: f64) -> String {
+        match self.currency.as_str() {
+            "DKK" => format!("{:.2} kr", amount),
+            currency => format!("{:.2} {}", amount, currency),
+        }
+    }
+
+    /// Unit price in the canonical unit, as a range if it depends on the size or amount
+    /// bought, e.g. `78.00-97.50 kr/kg`.
+    pub fn format_cost_per_unit(&self) -> Option<String> {
+        let unit = self.unit?;
+        let cost = self.cost_per_unit?;
+        if cost.is_range() {
+            Some(format!(
+                "{:.2}-{}/{}",
+                cost.min,
+                self.format_amount(cost.max),
+                unit
+            ))
+        } else {
+            Some(format!("{}/{}", self.format_amount(cost.min), unit))
+        }
+    }
+
+    /// The offer price, stating how many items it covers for multi-buy offers.
+    pub fn format_price(&self) -> String {
+        match self.multi_buy {
+            Some(count) => format!("{} for {}", count, self.format_amount(self.price)),
+            None => self.format_amount(self.price),
+        }
+    }
+
+    /// Description and catalog page of the offer, for the details column.
+    pub fn details(&self) -> String {
+        let mut details = self.description.clone().unwrap_or_default();
+        if let Some(page) = self.catalog_page {
+            if !details.is_empty() {
+                details.push('\n');
+            }
+            details.push_str(&format!("Catalog page {page}"));
+        }
+        if details.is_empty() {
+            "-".to_string()
+        } else {
+            details
+        }
+    }
+
+    pub fn to_table_entry(&self) -> Vec<Cell> {
+        let unit = self.unit.map(|unit| unit.symbol()).unwrap_or("");
         let period = format!(
             "{}\n  ↓  \n{}",
             self.run_from.format("%d/%m"),
             self.run_till.format("%d/%m")
         );
-        let cost_per_unit = format!("{:.2} kr/{}", self.cost_per_unit, unit);
-        let price = format!("{:.2} kr", self.price);
+        let cost_per_unit = self
+            .format_cost_per_unit()
+            .unwrap_or_else(|| "-".to_string());
+        let price = match self.pre_price {
+            Some(pre_price) if pre_price > self.price => format!(
+                "{}\n(was {})",
+                self.format_price(),
+                self.format_amount(pre_price)
+            ),
+            _ => self.format_price(),
+        };
+        let savings = match (self.savings, self.discount) {
+            (Some(savings), Some(discount)) => {
+                format!("-{:.0}%\n{}", discount, self.format_amount(savings))
+            }
+            _ => "-".to_string(),
+        };
         let count = if self.min_amount == self.max_amount {
             format!("{}", self.min_amount)
         } else {
@@ -84,7 +495,9 @@ impl Offer {
             format!("{}", self.max_size)
         };
 
-        let weight = if max_size_equals_min_size {
+        let size = if self.unit.is_none() || self.max_size == 0.0 {
+            "-".to_string()
+        } else if max_size_equals_min_size {
             format!("{} {}", min_size, unit)
         } else {
             format!("{}-{} {}", min_size, max_size, unit)
@@ -96,8 +509,54 @@ impl Offer {
             Cell::new(self.name.to_string()),
             Cell::new(count),
             Cell::new(price).set_alignment(CellAlignment::Right),
+            Cell::new(savings).set_alignment(CellAlignment::Right),
             Cell::new(cost_per_unit).set_alignment(CellAlignment::Right),
-            Cell::new(weight).set_alignment(CellAlignment::Right),
+            Cell::new(size).set_alignment(CellAlignment::Right),
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::deserialize::deserialize_hotspots;
+
+    fn offer(id: &str, name: &str) -> Offer {
+        let hotspot = serde_json::json!({"offer": {
+            "id": id,
+            "heading": name,
+            "pricing": {"price": 20.0},
+            "run_from": "2026-10-11T00:00:00+0000",
+            "run_till": "2026-10-24T23:59:59+0000"
+        }});
+        let dealer = Dealer::new("9ba51", "Netto");
+        deserialize_hotspots(vec![hotspot], &dealer, "c1")
+            .offers
+            .remove(0)
+    }
+
+    fn ids(offers: &[Offer]) -> Vec<&str> {
+        offers.iter().map(|offer| offer.id.as_str()).collect()
+    }
+
+    #[test]
+    fn leaves_out_repeated_ids_and_products() {
+        let mut offers = vec![
+            offer("a", "Kaffe"),
+            offer("b", "Smør"),
+            offer("a", "Kaffe"),
+            offer("c", "Kaffe"),
+        ];
+        assert_eq!(dedup(&mut offers), 2);
+        assert_eq!(ids(&offers), ["a", "b"]);
+    }
+
+    #[test]
+    fn compares_with_kept_offers_only() {
+        // `b` equals `a` by id and `c` by product, but `a` and `c` differ, so `c` stays.
+        let mut offers = vec![offer("a", "Kaffe"), offer("a", "Te"), offer("c", "Te")];
+        assert_eq!(dedup(&mut offers), 1);
+        assert_eq!(ids(&offers), ["a", "c"]);
+        assert_eq!(offers[1].name, "Te");
+    }
+}
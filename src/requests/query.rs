@@ -0,0 +1,666 @@
+//! Search queries such as `name:/^økologisk/ dealer:netto price<20 unit:kg`.
+//!
+//! Plain words match offer names loosely, and words next to each other are OR'ed, so
+//! `kaffe te` finds both. Fields narrow the search down and are AND'ed with everything
+//! next to them:
+//!
+//! - `name:kaffe`, `name:"hakket oksekød"` or `name:/^øko/` for a regular expression,
+//!   which may also be given as a bare `/^øko/`
+//! - `dealer:netto` or `dealer:netto,lidl`, by name or id
+//! - `price<20`, `unitprice<=50/kg` and `discount>=30`, with `<`, `<=`, `=`, `>=` or `>`
+//! - `unit:kg` for offers measured in kilograms, `unit:l` for liters and so on
+//! - `valid:2026-10-20` for offers valid on a date
+//! - `category:meat` for offers in a category
+//!
+//! Terms can be combined explicitly with `AND`, `OR`, `NOT` and parentheses.
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use regex::RegexBuilder;
+
+use super::{
+    category::Category,
+    filter::{Comparison, OfferFilter},
+    matcher::NameMatcher,
+    unit::{Dimension, UnitPrice},
+};
+
+const FIELDS: &[&str] = &[
+    "name",
+    "dealer",
+    "price",
+    "unitprice",
+    "discount",
+    "unit",
+    "valid",
+    "category",
+];
+
+/// A query that could not be parsed, pointing at the offending part of it.
+#[derive(Debug)]
+pub struct QueryError {
+    pub query: String,
+    /// Position of the offending part, in characters.
+    pub position: usize,
+    pub length: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "    {}", self.query)?;
+        write!(
+            f,
+            "    {}{}",
+            " ".repeat(self.position),
+            "^".repeat(self.length.max(1))
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Parse search terms given as separate arguments into a filter.
+pub fn parse_query(terms: &[String]) -> Result<OfferFilter, QueryError> {
+    let query = terms.join(" ");
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    for term in terms {
+        lex_term(&query, term, offset, &mut tokens)?;
+        offset += term.len() + 1;
+    }
+    if tokens.is_empty() {
+        return Ok(OfferFilter::All);
+    }
+
+    let mut parser = Parser {
+        query: &query,
+        tokens,
+        position: 0,
+    };
+    let filter = parser.parse_or()?;
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(parser.error(token.span, "Unmatched `)`")),
+        None => Ok(filter),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+#[derive(Debug)]
+enum TokenKind {
+    Word(String),
+    Field {
+        name: String,
+        operator: String,
+        operator_span: Span,
+        value: String,
+        value_span: Span,
+    },
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn error_at(query: &str, span: Span, message: impl Into<String>) -> QueryError {
+    QueryError {
+        query: query.to_string(),
+        position: query[..span.start].chars().count(),
+        length: query[span.start..span.end].chars().count(),
+        message: message.into(),
+    }
+}
+
+/// Split one argument into tokens. An argument with spaces but no query syntax, such as
+/// `"hakket oksekød"`, is kept as a single phrase.
+fn lex_term(
+    query: &str,
+    term: &str,
+    offset: usize,
+    tokens: &mut Vec<Token>,
+) -> Result<(), QueryError> {
+    let trimmed = term.trim();
+    let is_phrase = trimmed.contains(char::is_whitespace)
+        && !trimmed.contains([':', '<', '>', '=', '"', '/', '(', ')'])
+        && !trimmed
+            .split_whitespace()
+            .any(|word| matches!(word, "AND" | "OR" | "NOT"));
+    if is_phrase {
+        let start = offset + (term.len() - term.trim_start().len());
+        tokens.push(Token {
+            kind: TokenKind::Word(trimmed.to_string()),
+            span: Span {
+                start,
+                end: start + trimmed.len(),
+            },
+        });
+        return Ok(());
+    }
+
+    let chars: Vec<(usize, char)> = term.char_indices().collect();
+    let position = |index: usize| offset + chars.get(index).map_or(term.len(), |(at, _)| *at);
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' || c == ')' {
+            tokens.push(Token {
+                kind: if c == '(' {
+                    TokenKind::Open
+                } else {
+                    TokenKind::Close
+                },
+                span: Span {
+                    start: position(i),
+                    end: position(i + 1),
+                },
+            });
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < chars.len() {
+            let c = chars[i].1;
+            if c.is_whitespace() || c == '(' || c == ')' {
+                break;
+            }
+            // Quotes and regular expressions may contain spaces and parentheses.
+            if c == '"' || (c == '/' && (i == start || chars[i - 1].1 == ':')) {
+                let close = (i + 1..chars.len())
+                    .find(|&k| chars[k].1 == c && (c == '"' || chars[k - 1].1 != '\\'));
+                match close {
+                    Some(k) => i = k + 1,
+                    None => {
+                        let span = Span {
+                            start: position(i),
+                            end: position(chars.len()),
+                        };
+                        return Err(error_at(query, span, format!("Missing closing `{c}`")));
+                    }
+                }
+                continue;
+            }
+            i += 1;
+        }
+        let span = Span {
+            start: position(start),
+            end: position(i),
+        };
+        tokens.push(classify(query, span)?);
+    }
+    Ok(())
+}
+
+fn classify(query: &str, span: Span) -> Result<Token, QueryError> {
+    let text = &query[span.start..span.end];
+    let kind = match text {
+        "AND" => TokenKind::And,
+        "OR" => TokenKind::Or,
+        "NOT" => TokenKind::Not,
+        // A bare regular expression is short for `name:/…/`.
+        _ if text.starts_with('/') => TokenKind::Field {
+            name: "name".to_string(),
+            operator: ":".to_string(),
+            operator_span: Span {
+                start: span.start,
+                end: span.start,
+            },
+            value: text.to_string(),
+            value_span: span,
+        },
+        _ => match text.find([':', '<', '>', '=']) {
+            Some(at) if at > 0 && text[..at].chars().all(|c| c.is_alphabetic() || c == '-') => {
+                let name = text[..at].to_lowercase().replace('-', "");
+                if !FIELDS.contains(&name.as_str()) {
+                    let field_span = Span {
+                        start: span.start,
+                        end: span.start + at,
+                    };
+                    return Err(error_at(
+                        query,
+                        field_span,
+                        format!(
+                            "Unknown field `{}`, expected one of: {}",
+                            &text[..at],
+                            FIELDS.join(", ")
+                        ),
+                    ));
+                }
+                let operator_length =
+                    if text[at..].starts_with("<=") || text[at..].starts_with(">=") {
+                        2
+                    } else {
+                        1
+                    };
+                let value_start = at + operator_length;
+                TokenKind::Field {
+                    name,
+                    operator: text[at..value_start].to_string(),
+                    operator_span: Span {
+                        start: span.start + at,
+                        end: span.start + value_start,
+                    },
+                    value: text[value_start..].to_string(),
+                    value_span: Span {
+                        start: span.start + value_start,
+                        end: span.end,
+                    },
+                }
+            }
+            _ => TokenKind::Word(unquote(text).to_string()),
+        },
+    };
+    Ok(Token { kind, span })
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+/// Recursive descent parser, where `NOT` binds tightest, then terms next to each other,
+/// then `AND` and finally `OR`.
+struct Parser<'a> {
+    query: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, span: Span, message: impl Into<String>) -> QueryError {
+        error_at(self.query, span, message)
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.position).map(|token| &token.kind)
+    }
+
+    fn parse_or(&mut self) -> Result<OfferFilter, QueryError> {
+        let mut filter = self.parse_and()?;
+        while let Some(TokenKind::Or) = self.peek() {
+            self.position += 1;
+            filter = filter.or(self.parse_and()?);
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<OfferFilter, QueryError> {
+        let mut filter = self.parse_sequence()?;
+        while let Some(TokenKind::And) = self.peek() {
+            self.position += 1;
+            filter = filter.and(self.parse_sequence()?);
+        }
+        Ok(filter)
+    }
+
+    /// Terms next to each other, where plain words are OR'ed and everything else is
+    /// AND'ed, so `kaffe te dealer:netto` finds coffee or tea from Netto.
+    fn parse_sequence(&mut self) -> Result<OfferFilter, QueryError> {
+        let mut words: Option<OfferFilter> = None;
+        let mut others = OfferFilter::All;
+        loop {
+            let (filter, is_word) = self.parse_unary()?;
+            if is_word {
+                words = Some(match words {
+                    Some(words) => words.or(filter),
+                    None => filter,
+                });
+            } else {
+                others = others.and(filter);
+            }
+            if matches!(
+                self.peek(),
+                None | Some(TokenKind::And | TokenKind::Or | TokenKind::Close)
+            ) {
+                break;
+            }
+        }
+        Ok(words.unwrap_or(OfferFilter::All).and(others))
+    }
+
+    /// Parse a single term, telling whether it was a plain word.
+    fn parse_unary(&mut self) -> Result<(OfferFilter, bool), QueryError> {
+        let Some(token) = self.tokens.get(self.position) else {
+            let span = match self.tokens.last() {
+                Some(last) => last.span,
+                None => Span { start: 0, end: 0 },
+            };
+            let operator = &self.query[span.start..span.end];
+            return Err(self.error(span, format!("Expected a search term after `{operator}`")));
+        };
+        let span = token.span;
+        self.position += 1;
+        match &self.tokens[self.position - 1].kind {
+            TokenKind::Word(word) => Ok((OfferFilter::Name(NameMatcher::new(word)), true)),
+            TokenKind::Not => Ok((self.parse_unary()?.0.negate(), false)),
+            TokenKind::Open => {
+                let filter = self.parse_or()?;
+                match self.peek() {
+                    Some(TokenKind::Close) => {
+                        self.position += 1;
+                        Ok((filter, false))
+                    }
+                    _ => Err(self.error(span, "Missing closing `)`")),
+                }
+            }
+            TokenKind::Close => Err(self.error(span, "Unmatched `)`")),
+            TokenKind::And | TokenKind::Or => {
+                let operator = &self.query[span.start..span.end];
+                Err(self.error(span, format!("Expected a search term before `{operator}`")))
+            }
+            TokenKind::Field {
+                name,
+                operator,
+                operator_span,
+                value,
+                value_span,
+            } => {
+                let field = Field {
+                    parser: self,
+                    name,
+                    operator,
+                    operator_span: *operator_span,
+                    value,
+                    value_span: *value_span,
+                };
+                Ok((field.into_filter()?, false))
+            }
+        }
+    }
+}
+
+/// A `field:value` or `field<value` term.
+struct Field<'a> {
+    parser: &'a Parser<'a>,
+    name: &'a str,
+    operator: &'a str,
+    operator_span: Span,
+    value: &'a str,
+    value_span: Span,
+}
+
+impl Field<'_> {
+    fn value_error(&self, message: impl Into<String>) -> QueryError {
+        let span = if self.value.is_empty() {
+            Span {
+                start: self.operator_span.start,
+                end: self.value_span.end,
+            }
+        } else {
+            self.value_span
+        };
+        self.parser.error(span, message)
+    }
+
+    /// Fields that only match, like `name:`, take no comparison.
+    fn expect_match(&self) -> Result<(), QueryError> {
+        match self.operator {
+            ":" | "=" => Ok(()),
+            operator => Err(self.parser.error(
+                self.operator_span,
+                format!(
+                    "`{}` cannot be compared with `{operator}`, use `:`",
+                    self.name
+                ),
+            )),
+        }
+    }
+
+    fn comparison(&self) -> Comparison {
+        match self.operator {
+            "<" => Comparison::Less,
+            "<=" => Comparison::AtMost,
+            ">=" => Comparison::AtLeast,
+            ">" => Comparison::Greater,
+            _ => Comparison::Equal,
+        }
+    }
+
+    fn number(&self, value: &str) -> Result<f64, QueryError> {
+        value.trim().parse().map_err(|_| {
+            self.value_error(format!(
+                "Expected a number after `{}{}`",
+                self.name, self.operator
+            ))
+        })
+    }
+
+    fn into_filter(self) -> Result<OfferFilter, QueryError> {
+        if self.value.is_empty() {
+            return Err(self.value_error(format!(
+                "Expected a value after `{}{}`",
+                self.name, self.operator
+            )));
+        }
+        match self.name {
+            "name" => {
+                self.expect_match()?;
+                match self.value.strip_prefix('/') {
+                    Some(pattern) => {
+                        let Some(pattern) = pattern.strip_suffix('/') else {
+                            return Err(self.value_error("Missing closing `/`"));
+                        };
+                        RegexBuilder::new(&pattern.replace("\\/", "/"))
+                            .case_insensitive(true)
+                            .build()
+                            .map(OfferFilter::NamePattern)
+                            .map_err(|err| {
+                                let reason = err.to_string();
+                                let reason = reason.lines().last().unwrap_or_default();
+                                let reason = reason.trim().trim_start_matches("error: ");
+                                self.value_error(format!("Invalid regular expression: {reason}"))
+                            })
+                    }
+                    None => Ok(OfferFilter::Name(NameMatcher::new(unquote(self.value)))),
+                }
+            }
+            "dealer" => {
+                self.expect_match()?;
+                Ok(unquote(self.value)
+                    .split(',')
+                    .filter(|dealer| !dealer.trim().is_empty())
+                    .map(|dealer| OfferFilter::DealerName(dealer.trim().to_string()))
+                    .reduce(OfferFilter::or)
+                    .unwrap_or(OfferFilter::All))
+            }
+            "price" => {
+                let price = self.number(self.value.trim_end_matches("kr"))?;
+                Ok(OfferFilter::Price(self.comparison(), price))
+            }
+            "unitprice" => {
+                let unit_price: UnitPrice = self
+                    .value
+                    .parse()
+                    .map_err(|err: anyhow::Error| self.value_error(err.to_string()))?;
+                Ok(OfferFilter::UnitPrice(self.comparison(), unit_price))
+            }
+            "discount" => {
+                let discount = self.number(self.value.trim_end_matches('%'))?;
+                Ok(OfferFilter::Discount(self.comparison(), discount))
+            }
+            "unit" => {
+                self.expect_match()?;
+                match Dimension::parse(self.value) {
+                    Some((dimension, _)) => Ok(OfferFilter::Unit(dimension)),
+                    None => Err(self.value_error(format!(
+                        "Unknown unit `{}`, expected e.g. kg, l, stk or m",
+                        self.value
+                    ))),
+                }
+            }
+            "valid" => {
+                self.expect_match()?;
+                NaiveDate::parse_from_str(self.value, "%Y-%m-%d")
+                    .map(OfferFilter::ValidOn)
+                    .map_err(|_| self.value_error("Expected a date like `2026-10-20`"))
+            }
+            "category" => {
+                self.expect_match()?;
+                Category::from_str(self.value, true)
+                    .map(OfferFilter::Category)
+                    .map_err(|_| {
+                        let categories: Vec<_> = Category::value_variants()
+                            .iter()
+                            .filter_map(|category| category.to_possible_value())
+                            .map(|value| value.get_name().to_string())
+                            .collect();
+                        self.value_error(format!(
+                            "Unknown category `{}`, expected one of: {}",
+                            self.value,
+                            categories.join(", ")
+                        ))
+                    })
+            }
+            _ => unreachable!("fields are checked when lexing"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dealer, Offer, requests::deserialize::deserialize_hotspots};
+
+    /// An offer from the API of `grams` grams of `name` for `price`.
+    fn offer(dealer: &str, name: &str, price: f64, grams: f64) -> Offer {
+        let hotspot = serde_json::json!({"offer": {
+            "id": format!("{dealer}-{name}-{price}"),
+            "heading": name,
+            "pricing": {"price": price},
+            "quantity": {
+                "unit": {"symbol": "g", "si": {"symbol": "kg", "factor": 0.001}},
+                "size": {"from": grams, "to": grams}
+            },
+            "run_from": "2026-10-11T00:00:00+0000",
+            "run_till": "2026-10-24T23:59:59+0000"
+        }});
+        let dealer = match dealer {
+            "lidl" => Dealer::new("71c90", "Lidl"),
+            _ => Dealer::new("9ba51", "Netto"),
+        };
+        let mut retrieved = deserialize_hotspots(vec![hotspot], &dealer, "c1");
+        assert!(retrieved.malformed.is_empty());
+        retrieved.offers.remove(0)
+    }
+
+    fn parse(query: &str) -> OfferFilter {
+        let terms: Vec<_> = query.split(' ').map(str::to_string).collect();
+        parse_query(&terms).unwrap()
+    }
+
+    fn error(terms: &[&str]) -> QueryError {
+        let terms: Vec<_> = terms.iter().map(|term| term.to_string()).collect();
+        parse_query(&terms).unwrap_err()
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert!(matches!(parse_query(&[]), Ok(OfferFilter::All)));
+    }
+
+    #[test]
+    fn words_next_to_each_other_are_ored() {
+        let filter = parse("kaffe te");
+        assert!(filter.matches(&offer("netto", "Kaffe", 39.0, 500.0)));
+        assert!(filter.matches(&offer("netto", "Te", 20.0, 100.0)));
+        assert!(!filter.matches(&offer("netto", "Smør", 20.0, 250.0)));
+    }
+
+    #[test]
+    fn fields_are_anded_with_their_neighbours() {
+        let filter = parse("kaffe price<40");
+        assert!(filter.matches(&offer("netto", "Kaffe", 39.0, 500.0)));
+        assert!(!filter.matches(&offer("netto", "Kaffe", 45.0, 500.0)));
+        assert!(!filter.matches(&offer("netto", "Te", 20.0, 100.0)));
+
+        let filter = parse("kaffe unitprice<=80/kg dealer:netto");
+        assert!(filter.matches(&offer("netto", "Kaffe", 39.0, 500.0)));
+        assert!(!filter.matches(&offer("netto", "Kaffe", 39.0, 400.0)));
+        assert!(!filter.matches(&offer("lidl", "Kaffe", 39.0, 500.0)));
+    }
+
+    #[test]
+    fn explicit_operators_and_parentheses() {
+        let filter = parse("(kaffe OR te) NOT dealer:lidl");
+        assert!(filter.matches(&offer("netto", "Kaffe", 39.0, 500.0)));
+        assert!(filter.matches(&offer("netto", "Te", 20.0, 100.0)));
+        assert!(!filter.matches(&offer("lidl", "Kaffe", 35.0, 400.0)));
+        assert!(!filter.matches(&offer("netto", "Smør", 20.0, 250.0)));
+    }
+
+    #[test]
+    fn phrases_and_regular_expressions() {
+        let terms = ["name:\"hakket oksekød\"".to_string()];
+        let filter = parse_query(&terms).unwrap();
+        assert!(filter.matches(&offer("netto", "Hakket oksekød 8-12%", 45.0, 500.0)));
+        assert!(!filter.matches(&offer("netto", "Oksekød i tern", 45.0, 500.0)));
+
+        for query in ["name:/^øko/", "/^øko/"] {
+            let filter = parse(query);
+            assert!(filter.matches(&offer("lidl", "Økologisk letmælk", 12.0, 1000.0)));
+            assert!(!filter.matches(&offer("lidl", "Letmælk, økologisk", 12.0, 1000.0)));
+        }
+
+        // Like a field, a bare regular expression narrows down the words next to it.
+        let filter = parse("mælk /^øko/");
+        assert!(filter.matches(&offer("lidl", "Økologisk letmælk", 12.0, 1000.0)));
+        assert!(!filter.matches(&offer("lidl", "Økologisk smør", 12.0, 1000.0)));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_part() {
+        let cases: &[(&[&str], usize, usize, &str)] = &[
+            (&["colour:red"], 0, 6, "Unknown field `colour`"),
+            (&["(kaffe"], 0, 1, "Missing closing `)`"),
+            (&["kaffe", ")"], 6, 1, "Unmatched `)`"),
+            (&["name:/[/"], 5, 3, "Invalid regular expression"),
+            (&["kaffe", "/[/"], 6, 3, "Invalid regular expression"),
+            (&["/a/b"], 0, 4, "Missing closing `/`"),
+            (&["price<abc"], 6, 3, "Expected a number after `price<`"),
+            (
+                &["kaffe", "AND"],
+                6,
+                3,
+                "Expected a search term after `AND`",
+            ),
+            (&["unit:foo"], 5, 3, "Unknown unit `foo`"),
+        ];
+        for (terms, position, length, message) in cases {
+            let error = error(terms);
+            assert_eq!(
+                (error.position, error.length),
+                (*position, *length),
+                "{terms:?}"
+            );
+            assert!(error.message.starts_with(message), "{}", error.message);
+        }
+    }
+
+    #[test]
+    fn error_positions_count_letters() {
+        let error = error(&["smør", "farve:rød"]);
+        assert_eq!((error.position, error.length), (5, 5));
+        assert_eq!(
+            error.to_string().lines().skip(1).collect::<Vec<_>>(),
+            ["    smør farve:rød", "         ^^^^^"]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for another etb process to finish with the user data.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Replace the file at `path` with `contents`, so that readers see either the old or
+/// the new contents, never a partial write.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().context("File has no parent directory")?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let name = path
+        .file_name()
+        .context("File has no name")?
+        .to_string_lossy();
+    // The temporary file must be on the same file system for the rename to be atomic.
+    let temporary = dir.join(format!(".{name}.{}.tmp", std::process::id()));
+
+    let written = File::create(&temporary).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match written.and_then(|_| std::fs::rename(&temporary, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = std::fs::remove_file(&temporary);
+            Err(err).with_context(|| format!("Failed to write {}", path.display()))
+        }
+    }
+}
+
+/// Advisory lock on the user data, held from reading it until the last write, so that
+/// concurrent etb processes do not overwrite each other's changes. Released on drop.
+#[derive(Debug)]
+pub struct DataLock {
+    _file: File,
+}
+
+impl DataLock {
+    /// Lock the user data through the lock file at `path`, waiting up to `timeout` for
+    /// another process holding it.
+    pub async fn acquire(path: &Path, timeout: Duration) -> Result<DataLock> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+        let start = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(DataLock { _file: file }),
+                Err(TryLockError::WouldBlock) if start.elapsed() < timeout => {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err(TryLockError::WouldBlock) => bail!(
+                    "Gave up after waiting {} seconds for another etb process to finish",
+                    timeout.as_secs()
+                ),
+                Err(TryLockError::Error(err)) => {
+                    return Err(err).with_context(|| format!("Failed to lock {}", path.display()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("etb-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_the_file_without_leaving_a_temporary_file() {
+        let dir = temp_dir("write");
+        let path = dir.join("nested/cache.json");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        let contents = std::fs::read_to_string(&path);
+        let files: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(contents.unwrap(), "new");
+        assert_eq!(files, ["cache.json"]);
+    }
+
+    #[test]
+    fn keeps_the_old_file_when_writing_fails() {
+        let dir = temp_dir("write-fail");
+        let path = dir.join("cache.json");
+        write_atomic(&path, b"old").unwrap();
+        // A directory in the way of the temporary file makes the write fail.
+        let temporary = dir.join(format!(".cache.json.{}.tmp", std::process::id()));
+        std::fs::create_dir(&temporary).unwrap();
+        let result = write_atomic(&path, b"new");
+        let contents = std::fs::read_to_string(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(contents.unwrap(), "old");
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_lock_then_times_out() {
+        let dir = temp_dir("lock");
+        let path = dir.join("lock");
+        let held = DataLock::acquire(&path, Duration::ZERO).await.unwrap();
+        let start = Instant::now();
+        let second = DataLock::acquire(&path, Duration::from_millis(300)).await;
+        let waited = start.elapsed();
+        drop(held);
+        let after_release = DataLock::acquire(&path, Duration::ZERO).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let err = second.unwrap_err().to_string();
+        assert!(err.contains("another etb process"), "{err}");
+        assert!(waited >= Duration::from_millis(300));
+        assert!(after_release.is_ok());
+    }
+}
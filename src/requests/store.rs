@@ -0,0 +1,80 @@
+use chrono::{NaiveTime, Weekday};
+use serde::Serialize;
+
+use super::{
+    client::Client,
+    dealer::Dealer,
+    deserialize::{RawStore, deserialize_store},
+    location::Location,
+};
+use crate::error::Result;
+
+/// A physical store of a dealer.
+#[derive(Debug, Clone, Serialize)]
+pub struct Store {
+    pub id: String,
+    pub dealer: Dealer,
+    pub street: String,
+    pub zip_code: String,
+    pub city: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Distance from the location the store was retrieved for, in meters.
+    pub distance: f64,
+    pub opening_hours: Vec<OpeningHours>,
+}
+
+/// Opening hours of a store on a given day of the week.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpeningHours {
+    pub day: Weekday,
+    pub opens: NaiveTime,
+    pub closes: NaiveTime,
+}
+
+impl Store {
+    /// Fetch the stores of `dealer` within the radius of `location`, closest first.
+    pub async fn retrieve_near(
+        client: &Client,
+        dealer: &Dealer,
+        location: &Location,
+    ) -> Result<Vec<Store>> {
+        let location_query = location.query();
+        let mut query = vec![("dealer_ids", dealer.id.as_str())];
+        query.extend(
+            location_query
+                .iter()
+                .map(|(key, value)| (*key, value.as_str())),
+        );
+
+        let mut stores: Vec<_> = client
+            .get_paginated::<RawStore>("/v2/stores", &query)
+            .await?
+            .items
+            .into_iter()
+            .map(|raw| deserialize_store(raw, dealer, location))
+            .collect();
+        stores.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        Ok(stores)
+    }
+
+    pub fn address(&self) -> String {
+        format!("{}, {} {}", self.street, self.zip_code, self.city)
+    }
+
+    /// Opening hours, one day per line, e.g. `Mon 07:00-22:00`.
+    pub fn opening_hours_summary(&self) -> String {
+        self.opening_hours
+            .iter()
+            .map(|hours| {
+                format!(
+                    "{} {}-{}",
+                    hours.day,
+                    hours.opens.format("%H:%M"),
+                    hours.closes.format("%H:%M")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::Context;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+
+/// What an offer is measured in, each with a canonical unit that unit prices are
+/// given in: kr/kg, kr/l, kr/stk and kr/m.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dimension {
+    Mass,
+    Volume,
+    Count,
+    Length,
+}
+
+/// Unit symbols as used by the API and on price tags, with their dimension and size in
+/// canonical units.
+const UNITS: &[(&str, Dimension, f64)] = &[
+    ("mg", Dimension::Mass, 0.000_001),
+    ("g", Dimension::Mass, 0.001),
+    ("gr", Dimension::Mass, 0.001),
+    ("hg", Dimension::Mass, 0.1),
+    ("kg", Dimension::Mass, 1.0),
+    ("ml", Dimension::Volume, 0.001),
+    ("cl", Dimension::Volume, 0.01),
+    ("dl", Dimension::Volume, 0.1),
+    ("l", Dimension::Volume, 1.0),
+    ("ltr", Dimension::Volume, 1.0),
+    ("liter", Dimension::Volume, 1.0),
+    ("pcs", Dimension::Count, 1.0),
+    ("pc", Dimension::Count, 1.0),
+    ("piece", Dimension::Count, 1.0),
+    ("pieces", Dimension::Count, 1.0),
+    ("stk", Dimension::Count, 1.0),
+    ("st", Dimension::Count, 1.0),
+    ("mm", Dimension::Length, 0.001),
+    ("cm", Dimension::Length, 0.01),
+    ("m", Dimension::Length, 1.0),
+];
+
+impl Dimension {
+    /// Symbol of the canonical unit.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Dimension::Mass => "kg",
+            Dimension::Volume => "l",
+            Dimension::Count => "stk",
+            Dimension::Length => "m",
+        }
+    }
+
+    /// Look up a unit symbol, returning its dimension and how many canonical units one
+    /// of it is, e.g. `g` is 0.001 kg.
+    pub fn parse(symbol: &str) -> Option<(Dimension, f64)> {
+        let symbol = symbol.trim().trim_end_matches('.').to_lowercase();
+        UNITS
+            .iter()
+            .find(|(known, _, _)| *known == symbol)
+            .map(|(_, dimension, factor)| (*dimension, *factor))
+    }
+
+    /// Resolve the unit of an offer from the API, which gives the printed symbol along
+    /// with an SI symbol and the factor converting the printed unit into it.
+    pub(crate) fn from_api(
+        symbol: Option<&str>,
+        si_symbol: Option<&str>,
+        si_factor: Option<f64>,
+    ) -> Option<(Dimension, f64)> {
+        if let (Some(si_symbol), Some(si_factor)) = (si_symbol, si_factor)
+            && let Some((dimension, factor)) = Dimension::parse(si_symbol)
+        {
+            return Some((dimension, si_factor * factor));
+        }
+        Dimension::parse(symbol?)
+    }
+
+    /// Deserialize a unit leniently, treating unknown symbols as no unit, so cached
+    /// offers with units from older versions can still be read.
+    pub(crate) fn deserialize_lenient<'de, D>(deserializer: D) -> Result<Option<Self>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let symbol = Option::<String>::deserialize(deserializer)?;
+        Ok(symbol.and_then(|symbol| Dimension::parse(&symbol).map(|(dimension, _)| dimension)))
+    }
+}
+
+impl std::fmt::Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl Serialize for Dimension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.symbol())
+    }
+}
+
+impl<'de> Deserialize<'de> for Dimension {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let symbol = String::deserialize(deserializer)?;
+        Dimension::parse(&symbol)
+            .map(|(dimension, _)| dimension)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown unit: {symbol}")))
+    }
+}
+
+/// A price per unit, such as `50/kg` or `2.5/100g`, converted to the canonical unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitPrice {
+    pub amount: f64,
+    pub dimension: Dimension,
+}
+
+impl std::fmt::Display for UnitPrice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} kr/{}", self.amount, self.dimension)
+    }
+}
+
+impl FromStr for UnitPrice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amount, unit) = s
+            .split_once('/')
+            .with_context(|| format!("Expected a price per unit like `50/kg`, got: {s}"))?;
+        let amount: f64 = amount
+            .trim()
+            .trim_end_matches("kr")
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|amount| amount.is_finite())
+            .with_context(|| format!("Invalid price: {amount}"))?;
+        // Allow a quantity before the unit, as in `2.5/100g`.
+        let unit = unit.trim();
+        let split = unit
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(unit.len());
+        let quantity = match &unit[..split] {
+            "" => 1.0,
+            quantity => quantity
+                .parse::<f64>()
+                .ok()
+                .filter(|quantity| *quantity > 0.0 && quantity.is_finite())
+                .with_context(|| format!("Invalid quantity: {quantity}"))?,
+        };
+        let (dimension, factor) =
+            Dimension::parse(&unit[split..]).with_context(|| format!("Unknown unit: {unit}"))?;
+        Ok(UnitPrice {
+            amount: amount / (quantity * factor),
+            dimension,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> UnitPrice {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_price_per_canonical_unit() {
+        assert_eq!(
+            parse("50/kg"),
+            UnitPrice {
+                amount: 50.0,
+                dimension: Dimension::Mass
+            }
+        );
+        assert_eq!(parse(" 12.5 kr / l ").amount, 12.5);
+        assert_eq!(parse("3/stk").dimension, Dimension::Count);
+    }
+
+    #[test]
+    fn converts_to_canonical_unit() {
+        assert_eq!(parse("5/g").amount, 5000.0);
+        assert_eq!(parse("10/dl").amount, 100.0);
+        assert_eq!(parse("2/cm").amount, 200.0);
+    }
+
+    #[test]
+    fn divides_by_quantity_before_unit() {
+        let price = parse("2.5/100g");
+        assert_eq!(price.dimension, Dimension::Mass);
+        assert!((price.amount - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_invalid_prices() {
+        for invalid in [
+            "50",
+            "abc/kg",
+            "/kg",
+            "50/",
+            "50/parsec",
+            "5/0g",
+            "inf/kg",
+            "NaN/kg",
+        ] {
+            assert!(invalid.parse::<UnitPrice>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -1,154 +1,661 @@
-use anyhow::Context;
+use anyhow::{Context, Result, bail};
 use chrono::prelude::*;
-use std::{collections::HashSet, process::exit, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
 
 use serde::{Deserialize, Serialize};
 
-use super::dealer::Dealer;
-use crate::{Offer, output::OutputFormat};
-use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use super::{
+    cache::{DealerCacheStatus, DealerUpdate, OfferCache},
+    category::Category,
+    client::Client,
+    config::Config,
+    dealer::Dealer,
+    filter::OfferFilter,
+    location::Location,
+    migration::{CACHE_VERSION, LoadWarning, migrate_cache},
+    offer::{self, MalformedOffer, RetrievedOffers},
+    storage::{DataLock, LOCK_TIMEOUT, write_atomic},
+    store::Store,
+};
+use crate::{Offer, error::Error};
 use futures::future;
 
+/// File in the cache directory holding data fetched from the API.
+const CACHE_FILE: &str = "cache.json";
+
+/// File in the cache directory locked while the user data is in use.
+const LOCK_FILE: &str = "lock";
+
+/// File in the cache directory that held settings along with fetched data, before
+/// settings moved to the config file.
+const LEGACY_USERDATA_FILE: &str = "userdata.json";
+
+/// How many days the list of available dealers is cached for.
+const DEALER_CACHE_DAYS: i64 = 7;
+
+/// A favorite dealer whose offers could not be retrieved.
+#[derive(Debug)]
+pub struct DealerFailure {
+    pub dealer: Dealer,
+    pub error: Error,
+}
+
+/// Problems that did not stop offers from being retrieved, but left some out.
+#[derive(Debug, Default)]
+pub struct RetrievalIssues {
+    pub failures: Vec<DealerFailure>,
+    pub malformed: Vec<MalformedOffer>,
+    /// Number of offers left out for duplicating another offer.
+    pub duplicates: usize,
+    /// Listings cut short at [`ClientOptions::max_results`], which may be missing offers.
+    ///
+    /// [`ClientOptions::max_results`]: super::client::ClientOptions::max_results
+    pub truncated: Vec<String>,
+}
+
+/// Offers matching a search, along with what could not be retrieved.
+#[derive(Debug)]
+pub struct SearchResults<'a> {
+    pub offers: Vec<&'a Offer>,
+    pub issues: RetrievalIssues,
+}
+
+/// Settings from the config file along with data fetched from the API, which is kept in
+/// the cache directory.
 #[derive(Serialize, Deserialize)]
-pub(crate) struct UserData {
-    favorites: HashSet<Dealer>,
-    date_of_last_cache: NaiveDate,
-    offers: Vec<Offer>,
-    favorites_changed: bool,
+pub struct UserData {
+    /// Schema version of the cache file, see [`CACHE_VERSION`].
+    version: u32,
+    #[serde(skip)]
+    config: Config,
+    /// Offers of favorite dealers, by dealer and catalog.
+    #[serde(default)]
+    offer_cache: OfferCache,
+    #[serde(default)]
+    dealers: Vec<Dealer>,
+    #[serde(default = "unix_epoch")]
+    date_of_dealer_cache: NaiveDate,
+    /// Location used for this invocation only.
+    #[serde(skip)]
+    near: Option<Location>,
+    /// Offers of favorite dealers for `near`, which are not saved, so that the cache
+    /// stays valid for the saved location.
+    #[serde(skip)]
+    near_cache: OfferCache,
+    #[serde(skip)]
+    warnings: Vec<LoadWarning>,
+    /// Held while the user data is loaded, so other processes wait to read it until
+    /// this one is done writing.
+    #[serde(skip)]
+    _lock: Option<DataLock>,
+}
+
+fn unix_epoch() -> NaiveDate {
+    DateTime::UNIX_EPOCH.date_naive()
 }
 
 impl UserData {
-    pub fn print_favorites(&self, format: Option<OutputFormat>) {
-        let format = format.unwrap_or(OutputFormat::Table);
+    /// Settings from the config file.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
 
-        match format {
-            OutputFormat::Rss => {
-                eprintln!("Unsupported output format for favorites");
-                exit(1);
-            }
-            OutputFormat::Json => {
-                println!(
-                    "{}",
-                    serde_json::to_string(&self.favorites)
-                        .unwrap_or("Failed to Serialize dealers".to_string())
-                );
-            }
-            OutputFormat::Table => {
-                let mut table = Table::new();
-                table
-                    .load_preset(UTF8_FULL)
-                    .apply_modifier(UTF8_ROUND_CORNERS)
-                    .set_header(vec!["Favorites"]);
-
-                for favorite in &self.favorites {
-                    table.add_row(vec![favorite]);
-                }
-                println!("{}", table);
-            }
+    /// The currently set favorite dealers, sorted by name.
+    pub fn favorites(&self) -> Vec<Dealer> {
+        let mut favorites: Vec<_> = self.config.favorites.iter().cloned().collect();
+        favorites.sort_by(|a, b| a.name.cmp(&b.name));
+        favorites
+    }
+
+    /// The saved location, used for every request unless overridden.
+    pub fn location(&self) -> Option<Location> {
+        self.config.location
+    }
+
+    /// Set the saved location and write it to the config right away.
+    pub fn set_location(&mut self, location: Option<Location>) -> Result<()> {
+        if self.config.location == location {
+            return Ok(());
+        }
+        self.config.location = location;
+        self.save_config()
+    }
+
+    /// Use `location` instead of the saved location for this invocation only.
+    pub fn override_location(&mut self, location: Location) {
+        self.near = (self.config.location != Some(location)).then_some(location);
+    }
+
+    fn effective_location(&self) -> Option<Location> {
+        self.near.or(self.config.location)
+    }
+
+    /// Cached offers for the location of this invocation.
+    fn active_cache(&self) -> &OfferCache {
+        match self.near {
+            Some(_) => &self.near_cache,
+            None => &self.offer_cache,
         }
     }
 
-    pub(crate) fn from_cache() -> Option<UserData> {
-        let path = dirs::cache_dir()?.join("etilbudsavis-cli/userdata.json");
-        match std::fs::read_to_string(path) {
-            Ok(data) => serde_json::from_str(&data).ok(),
-            Err(_) => None,
+    fn active_cache_mut(&mut self) -> &mut OfferCache {
+        match self.near {
+            Some(_) => &mut self.near_cache,
+            None => &mut self.offer_cache,
         }
     }
 
-    fn update_cache(&mut self) -> anyhow::Result<()> {
-        self.date_of_last_cache = Utc::now().date_naive();
+    /// Read the config and the cache. Settings kept in the cache by older versions are
+    /// moved to the config file the first time.
+    ///
+    /// Files that cannot be read or migrated are backed up rather than overwritten, and
+    /// reported by [`UserData::load_warnings`].
+    pub async fn load() -> Result<UserData> {
+        let cache_dir = dirs::cache_dir().map(|dir| dir.join("etilbudsavis-cli"));
+        let lock = match &cache_dir {
+            Some(dir) => Some(DataLock::acquire(&dir.join(LOCK_FILE), LOCK_TIMEOUT).await?),
+            None => None,
+        };
+        let mut warnings = Vec::new();
+        let mut config = Config::load()?;
+        if config.is_none()
+            && let Some(dir) = &cache_dir
+        {
+            let config_path = Config::path().context("Could not find config dir")?;
+            config = move_legacy_userdata(dir, &config_path, &mut warnings)?;
+        }
+
+        let cache = cache_dir.and_then(|dir| load_cache(&dir.join(CACHE_FILE), &mut warnings));
+        let mut userdata = UserData {
+            config: config.unwrap_or_default(),
+            warnings,
+            _lock: lock,
+            ..cache.unwrap_or_default()
+        };
+        // Favorites may have been removed from the config by hand.
+        let favorites = &userdata.config.favorites;
+        userdata
+            .offer_cache
+            .retain(|dealer| favorites.contains(dealer));
+        Ok(userdata)
+    }
+
+    /// Files that could not be read when loading, and were set aside.
+    pub fn load_warnings(&self) -> &[LoadWarning] {
+        &self.warnings
+    }
+
+    fn update_cache(&self) -> Result<()> {
         let path = dirs::cache_dir()
             .context("Could not find cache dir")?
-            .join("etilbudsavis-cli");
-        std::fs::create_dir_all(&path)?;
-        std::fs::write(path.join("userdata.json"), serde_json::to_string(&self)?)?;
-        Ok(())
+            .join("etilbudsavis-cli")
+            .join(CACHE_FILE);
+        write_atomic(&path, serde_json::to_string(&self)?.as_bytes())
     }
 
-    pub(crate) fn add_favorites(&mut self, dealers: &[Dealer]) {
-        for &dealer in dealers {
-            self.favorites_changed |= self.favorites.insert(dealer)
+    fn save_config(&self) -> Result<()> {
+        self.config.save().context("Failed to save config")
+    }
+
+    /// Add favorites and write them to the config right away. Their offers are
+    /// retrieved on the next search.
+    pub fn add_favorites(&mut self, dealers: &[Dealer]) -> Result<()> {
+        let mut changed = false;
+        for dealer in dealers {
+            changed |= self.config.favorites.insert(dealer.clone());
         }
+        if changed { self.save_config() } else { Ok(()) }
     }
 
-    pub(crate) fn remove_favorites(&mut self, dealers: &[Dealer]) {
+    /// Remove favorites along with their cached offers, writing both right away.
+    pub fn remove_favorites(&mut self, dealers: &[Dealer]) -> Result<()> {
+        let mut changed = false;
         for dealer in dealers {
-            self.favorites_changed |= self.favorites.remove(dealer)
+            changed |= self.config.favorites.remove(dealer);
+            self.offer_cache.remove(dealer);
+        }
+        if changed {
+            self.save_config()?;
+            self.update_cache().context("Failed to update cache")?;
         }
+        Ok(())
+    }
+
+    /// How fresh the saved offers of each favorite are, for the saved location.
+    pub fn cache_status(&self) -> Vec<DealerCacheStatus> {
+        self.offer_cache.status(
+            &self.favorites(),
+            self.config.location,
+            Utc::now().date_naive(),
+        )
     }
 
     #[inline(always)]
-    fn cache_outdated(&self) -> bool {
-        self.date_of_last_cache < Utc::now().date_naive()
+    fn dealers_outdated(&self) -> bool {
+        self.dealers.is_empty()
+            || (Utc::now().date_naive() - self.date_of_dealer_cache).num_days() >= DEALER_CACHE_DAYS
+    }
+
+    async fn refresh_dealers(&mut self, client: &Client) -> Result<()> {
+        let mut dealers = Dealer::retrieve_all(client)
+            .await
+            .context("Failed to retrieve the list of dealers")?;
+        dealers.sort_by(|a, b| a.name.cmp(&b.name));
+        self.dealers = dealers;
+        self.date_of_dealer_cache = Utc::now().date_naive();
+        self.update_cache().context("Failed to update cache")
     }
 
-    /// Fetch offers if we have updates or the cache is too old.
-    pub(crate) async fn retrieve_offers(&mut self) {
-        if self.favorites_changed || self.cache_outdated() {
-            self.offers = self.retrieve_offers_from_remote().await;
-            if let Err(err) = self.update_cache() {
-                eprintln!("Failed to update cache: {}", err);
+    /// Every dealer available from the API, falling back to a stale list if it cannot
+    /// be refreshed.
+    pub async fn available_dealers(&mut self, client: &Client) -> Result<&[Dealer]> {
+        if self.dealers_outdated() {
+            let refreshed = self.refresh_dealers(client).await;
+            if self.dealers.is_empty() {
+                refreshed?;
             }
         }
+        Ok(&self.dealers)
     }
 
-    async fn retrieve_offers_from_remote(&mut self) -> Vec<Offer> {
-        let tasks: Vec<_> = self
-            .favorites
-            .iter()
+    /// Resolve dealer ids, names or slugs, refreshing the dealer list once if any of
+    /// them are unknown.
+    pub async fn resolve_dealers(
+        &mut self,
+        client: &Client,
+        queries: &[String],
+    ) -> Result<Vec<Dealer>> {
+        let mut refreshed = !self.dealers_outdated();
+        let mut dealers = Vec::with_capacity(queries.len());
+        for query in queries {
+            let mut dealer = self.resolve_dealer(query);
+            if dealer.is_none() && !refreshed {
+                refreshed = true;
+                if let Err(err) = self.refresh_dealers(client).await {
+                    return Err(err.context(format!("Unknown dealer: {query}")));
+                }
+                dealer = self.resolve_dealer(query);
+            }
+            dealers.push(dealer.with_context(|| {
+                format!("Unknown dealer: {query}.\nSee `dealers` for available dealers.")
+            })?);
+        }
+        Ok(dealers)
+    }
+
+    fn resolve_dealer(&self, query: &str) -> Option<Dealer> {
+        Dealer::resolve(query, &self.favorites())
+            .filter(|dealer| self.config.favorites.contains(dealer))
+            .or_else(|| Dealer::resolve(query, &self.dealers))
+    }
+
+    /// Bring the cached offers of favorites up to date. Only dealers not checked today
+    /// or cached for another location are asked for their catalogs, and only catalogs
+    /// that are not cached yet are retrieved.
+    ///
+    /// Dealers that fail keep their previous offers and are retried on the next
+    /// invocation. Offers for a location given with [`UserData::override_location`] are
+    /// retrieved afresh and not saved.
+    pub async fn retrieve_offers(&mut self, client: &Client) -> Result<RetrievalIssues> {
+        let today = Utc::now().date_naive();
+        let location = self.effective_location();
+        let outdated: Vec<_> = self
+            .favorites()
+            .into_iter()
+            .filter(|dealer| self.active_cache().outdated(dealer, location, today))
+            .collect();
+        if outdated.is_empty() {
+            return Ok(RetrievalIssues::default());
+        }
+
+        let tasks: Vec<_> = outdated
+            .into_iter()
             .map(|dealer| {
-                let dealer = *dealer;
-                tokio::spawn(async move { dealer.remote_offers_for_dealer().await })
+                let client = client.clone();
+                let cached = self.active_cache().catalog_ids(&dealer, location);
+                let task = tokio::spawn({
+                    let dealer = dealer.clone();
+                    async move {
+                        DealerUpdate::retrieve(&client, &dealer, location.as_ref(), &cached).await
+                    }
+                });
+                async move { (dealer, task.await) }
             })
             .collect();
 
-        future::join_all(tasks)
-            .await
-            .into_iter()
-            .flatten()
-            .flatten()
-            .collect()
+        let mut issues = RetrievalIssues::default();
+        let now = Utc::now();
+        for (dealer, result) in future::join_all(tasks).await {
+            match result.map_err(Error::from).and_then(|result| result) {
+                Ok(mut update) => {
+                    if update.catalogs_truncated {
+                        issues.truncated.push(format!("the catalogs of {dealer}"));
+                    }
+                    for (_, retrieved) in &mut update.retrieved {
+                        issues.malformed.append(&mut retrieved.malformed);
+                        issues.duplicates += retrieved.duplicates;
+                        issues.truncated.append(&mut retrieved.truncated);
+                    }
+                    self.active_cache_mut()
+                        .update(&dealer, location, update, now);
+                }
+                Err(error) => issues.failures.push(DealerFailure { dealer, error }),
+            }
+        }
+        if self.near.is_none() {
+            self.update_cache().context("Failed to update cache")?;
+        }
+        Ok(issues)
     }
 
-    pub async fn search(&mut self, search_items: &[String], search_by_dealer: bool) -> Vec<&Offer> {
-        if search_items.is_empty() {
-            self.retrieve_offers().await;
-            return self.offers.iter().collect();
+    /// Cached offers of favorites from catalogs that are still valid.
+    fn cached_offers(&self) -> impl Iterator<Item = &Offer> {
+        self.active_cache().offers(Utc::now().date_naive())
+    }
+
+    /// Stores of `dealers`, or of every favorite if none are given, closest to the
+    /// current location first.
+    pub async fn stores(&mut self, client: &Client, dealers: &[String]) -> Result<Vec<Store>> {
+        let location = self
+            .effective_location()
+            .context("No location set. Set one with `location` or use `--near`")?;
+        let dealers = if dealers.is_empty() {
+            self.favorites()
+        } else {
+            self.resolve_dealers(client, dealers).await?
+        };
+        if dealers.is_empty() {
+            bail!("No favorites set. Add one with `add` or name a dealer");
         }
 
-        search_items
+        let tasks = dealers
             .iter()
-            .flat_map(|search| {
-                if search_by_dealer {
-                    if let Ok(dealer) = Dealer::from_str(search) {
-                        self.offers
-                            .iter()
-                            .filter(|offer| offer.dealer == dealer)
-                            .collect::<Vec<_>>()
-                    } else {
-                        println!("Search term did not match any known dealers: {search}");
-                        Dealer::list_known_dealers(None);
-                        vec![]
-                    }
-                } else {
-                    self.offers
-                        .iter()
-                        .filter(|offer| offer.name.to_lowercase().contains(search.trim()))
-                        .collect::<Vec<_>>()
-                }
-            })
-            .collect()
+            .map(|dealer| Store::retrieve_near(client, dealer, &location));
+        let mut stores = Vec::new();
+        for (dealer, result) in dealers.iter().zip(future::join_all(tasks).await) {
+            stores
+                .extend(result.with_context(|| format!("Failed to retrieve stores of {dealer}"))?);
+        }
+        stores.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        Ok(stores)
+    }
+
+    /// Categories of the offers of favorite dealers, with how many offers are in each,
+    /// retrieving offers first if the cache is outdated.
+    pub async fn categories(
+        &mut self,
+        client: &Client,
+    ) -> Result<(Vec<(Category, usize)>, RetrievalIssues)> {
+        let issues = self.retrieve_offers(client).await?;
+        let mut counts = BTreeMap::new();
+        let mut offers: Vec<_> = self.cached_offers().collect();
+        offer::dedup(&mut offers);
+        for offer in offers {
+            for category in &offer.categories {
+                *counts.entry(*category).or_insert(0) += 1;
+            }
+        }
+        Ok((counts.into_iter().collect(), issues))
+    }
+
+    /// Search the offers of every dealer through the API instead of the cached offers
+    /// of favorite dealers, keeping those matching `filter`.
+    pub async fn search_remote(
+        &mut self,
+        client: &Client,
+        filter: &OfferFilter,
+    ) -> Result<RetrievedOffers> {
+        let search_items = filter.search_terms();
+        if search_items.is_empty() {
+            bail!("Searching every dealer requires at least one search term");
+        }
+
+        // Only used to name dealers, so a stale or missing list is fine.
+        let directory = match self.available_dealers(client).await {
+            Ok(dealers) => dealers.to_vec(),
+            Err(_) => Vec::new(),
+        };
+
+        let mut retrieved = RetrievedOffers::default();
+        for search in search_items {
+            let found = Offer::search_remote(
+                client,
+                search,
+                &directory,
+                self.effective_location().as_ref(),
+            )
+            .await
+            .with_context(|| format!("Failed to search for {search}"))?;
+            retrieved.extend(found);
+        }
+        retrieved.offers.retain(|offer| filter.matches(offer));
+        // Offers found by several terms are only kept once.
+        retrieved.dedup();
+        Ok(retrieved)
+    }
+
+    /// Offers of favorite dealers matching `filter`, retrieving offers first if the
+    /// cache is outdated.
+    pub async fn search(
+        &mut self,
+        client: &Client,
+        filter: &OfferFilter,
+    ) -> Result<SearchResults<'_>> {
+        let mut issues = self.retrieve_offers(client).await?;
+        let mut offers: Vec<_> = self
+            .cached_offers()
+            .filter(|offer| filter.matches(offer))
+            .collect();
+        // The same offer may appear in several catalogs of a dealer.
+        issues.duplicates += offer::dedup(&mut offers);
+        Ok(SearchResults { offers, issues })
     }
 }
 
 impl Default for UserData {
     fn default() -> Self {
         UserData {
-            date_of_last_cache: DateTime::UNIX_EPOCH.date_naive(),
-            favorites_changed: false,
-            favorites: HashSet::new(),
-            offers: Vec::new(),
+            version: CACHE_VERSION,
+            config: Config::default(),
+            offer_cache: OfferCache::default(),
+            dealers: Vec::new(),
+            date_of_dealer_cache: unix_epoch(),
+            near: None,
+            near_cache: OfferCache::default(),
+            warnings: Vec::new(),
+            _lock: None,
         }
     }
 }
+
+/// Settings as kept in the cache directory by older versions.
+#[derive(Deserialize)]
+struct LegacyUserData {
+    #[serde(default)]
+    favorites: HashSet<Dealer>,
+    #[serde(default)]
+    location: Option<Location>,
+}
+
+/// Read a file, or `None` if it does not exist.
+fn read_if_exists(path: &Path) -> Result<Option<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Read the settings kept in `userdata.json` by older versions into a config.
+fn read_legacy_userdata(path: &Path) -> Result<Option<Config>, String> {
+    let Some(data) = read_if_exists(path)? else {
+        return Ok(None);
+    };
+    let legacy: LegacyUserData =
+        serde_json::from_str(&data).map_err(|err| format!("invalid settings: {err}"))?;
+    Ok(Some(Config {
+        favorites: legacy.favorites.into_iter().collect(),
+        location: legacy.location,
+        ..Config::default()
+    }))
+}
+
+/// Move the settings in a legacy `userdata.json` in `cache_dir` to the config file at
+/// `config_path`, keeping the rest of the file as the cache file.
+fn move_legacy_userdata(
+    cache_dir: &Path,
+    config_path: &Path,
+    warnings: &mut Vec<LoadWarning>,
+) -> Result<Option<Config>> {
+    let legacy_path = cache_dir.join(LEGACY_USERDATA_FILE);
+    match read_legacy_userdata(&legacy_path) {
+        Ok(Some(legacy)) => {
+            legacy
+                .save_to(config_path)
+                .context("Failed to save migrated config")?;
+            // The fetched data in the same file is still of use.
+            let _ = std::fs::rename(&legacy_path, cache_dir.join(CACHE_FILE));
+            Ok(Some(legacy))
+        }
+        Ok(None) => Ok(None),
+        Err(reason) => {
+            warnings.push(LoadWarning::back_up(&legacy_path, reason));
+            Ok(None)
+        }
+    }
+}
+
+/// Read the cache file, backing it up if it cannot be read.
+fn load_cache(path: &Path, warnings: &mut Vec<LoadWarning>) -> Option<UserData> {
+    read_cache(path).unwrap_or_else(|reason| {
+        warnings.push(LoadWarning::back_up(path, reason));
+        None
+    })
+}
+
+/// Read the cache file, migrating it from older versions.
+fn read_cache(path: &Path) -> Result<Option<UserData>, String> {
+    let Some(data) = read_if_exists(path)? else {
+        return Ok(None);
+    };
+    let value = serde_json::from_str(&data).map_err(|err| format!("invalid JSON: {err}"))?;
+    let value = migrate_cache(value).map_err(|err| format!("migration failed: {err}"))?;
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|err| format!("unexpected contents: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("etb-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const V1_CACHE: &str = r#"{
+        "favorites": [{"id": "9ba51", "name": "Netto"}, "Lidl"],
+        "location": {"latitude": 55.68, "longitude": 12.57, "radius": 5000},
+        "offers": [],
+        "date_of_last_cache": "2026-10-01",
+        "dealers": [{"id": "9ba51", "name": "Netto"}],
+        "date_of_dealer_cache": "2026-10-01"
+    }"#;
+
+    #[test]
+    fn reads_a_v1_cache() {
+        let dir = temp_dir("read-v1");
+        let path = dir.join(CACHE_FILE);
+        std::fs::write(&path, V1_CACHE).unwrap();
+        let cache = read_cache(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let cache = cache.unwrap().unwrap();
+        assert_eq!(cache.version, CACHE_VERSION);
+        assert_eq!(cache.dealers, [Dealer::new("9ba51", "Netto")]);
+        assert_eq!(
+            cache.date_of_dealer_cache,
+            NaiveDate::from_ymd_opt(2026, 10, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_missing_cache_is_empty() {
+        let dir = temp_dir("read-missing");
+        let cache = read_cache(&dir.join(CACHE_FILE));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(cache.unwrap().is_none());
+    }
+
+    #[test]
+    fn backs_up_a_cache_from_a_newer_version() {
+        let dir = temp_dir("load-future");
+        let path = dir.join(CACHE_FILE);
+        let data = format!(r#"{{"version": {}, "dealers": []}}"#, CACHE_VERSION + 1);
+        std::fs::write(&path, &data).unwrap();
+        let mut warnings = Vec::new();
+        let cache = load_cache(&path, &mut warnings);
+        let backup = std::fs::read_to_string(dir.join("cache.json.bak"));
+        let still_there = path.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(cache.is_none());
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].reason.contains("another version"),
+            "{}",
+            warnings[0].reason
+        );
+        assert_eq!(warnings[0].backup, Some(dir.join("cache.json.bak")));
+        assert_eq!(backup.unwrap(), data);
+        assert!(!still_there);
+    }
+
+    #[test]
+    fn moves_legacy_userdata_to_the_config_and_cache() {
+        let dir = temp_dir("legacy");
+        std::fs::write(dir.join(LEGACY_USERDATA_FILE), V1_CACHE).unwrap();
+        let config_path = dir.join("config/config.toml");
+        let mut warnings = Vec::new();
+        let config = move_legacy_userdata(&dir, &config_path, &mut warnings);
+        let saved = std::fs::read_to_string(&config_path);
+        let legacy_left = dir.join(LEGACY_USERDATA_FILE).exists();
+        let cache = read_cache(&dir.join(CACHE_FILE));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap().unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            config.favorites,
+            [Dealer::new("9ba51", "Netto"), Dealer::new("71c90", "Lidl")].into()
+        );
+        assert_eq!(config.location, Some(Location::new(55.68, 12.57, 5000)));
+        let saved: Config = toml::from_str(&saved.unwrap()).unwrap();
+        assert_eq!(saved.favorites, config.favorites);
+        assert!(!legacy_left);
+        assert_eq!(cache.unwrap().unwrap().dealers.len(), 1);
+    }
+
+    #[test]
+    fn backs_up_invalid_legacy_userdata() {
+        let dir = temp_dir("legacy-invalid");
+        std::fs::write(dir.join(LEGACY_USERDATA_FILE), "{ not json").unwrap();
+        let config_path = dir.join("config.toml");
+        let mut warnings = Vec::new();
+        let config = move_legacy_userdata(&dir, &config_path, &mut warnings);
+        let backed_up = dir.join("userdata.json.bak").exists();
+        let config_written = config_path.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(config.unwrap().is_none());
+        assert_eq!(warnings.len(), 1);
+        assert!(backed_up);
+        assert!(!config_written);
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{NaiveDate, Utc};
+use etilbudsavis::{
+    Client, Dealer,
+    mock::{Fixtures, MockServer},
+    requests::{
+        cache::{DealerUpdate, OfferCache},
+        catalog::Catalog,
+        offer,
+        query::parse_query,
+    },
+};
+use std::collections::HashSet;
+
+/// Start a mock server in the background and return a client pointed at it.
+async fn serve(fixtures: Fixtures) -> Client {
+    let server = MockServer::bind(("127.0.0.1", 0), fixtures).await.unwrap();
+    let client = Client::with_base_url(server.url().unwrap());
+    tokio::spawn(server.run());
+    client
+}
+
+/// A day on which every bundled catalog is valid.
+fn catalog_day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 10, 20).unwrap()
+}
+
+#[tokio::test]
+async fn retrieves_dealers() {
+    let client = serve(Fixtures::Bundled).await;
+    let dealers = Dealer::retrieve_all(&client).await.unwrap();
+    assert!(dealers.iter().any(|dealer| dealer.id == "9ba51"));
+    assert!(dealers.iter().any(|dealer| dealer.id == "71c90"));
+}
+
+#[tokio::test]
+async fn skips_malformed_hotspots() {
+    let client = serve(Fixtures::Bundled).await;
+    let lidl = Dealer::new("71c90", "Lidl");
+    let catalogs = Catalog::retrieve_for_dealer(&client, &lidl, None)
+        .await
+        .unwrap();
+    assert!(!catalogs.truncated);
+    let ids: Vec<_> = catalogs.items.iter().map(|catalog| &catalog.id).collect();
+    assert_eq!(ids, ["c4f7aLm9"]);
+
+    let retrieved = catalogs.items[0].retrieve_offers(&client).await.unwrap();
+    assert_eq!(retrieved.offers.len(), 5);
+    assert!(retrieved.offers.iter().all(|offer| offer.dealer == lidl));
+    assert_eq!(retrieved.malformed.len(), 1);
+    let malformed = &retrieved.malformed[0];
+    assert_eq!(malformed.offer_id.as_deref(), Some("a77kU6gz"));
+    assert_eq!(malformed.catalog_id.as_deref(), Some("c4f7aLm9"));
+    assert_eq!(malformed.reason, "missing price");
+}
+
+#[tokio::test]
+async fn searches_cached_offers() {
+    let client = serve(Fixtures::Bundled).await;
+    let netto = Dealer::new("9ba51", "Netto");
+    let update = DealerUpdate::retrieve(&client, &netto, None, &HashSet::new())
+        .await
+        .unwrap();
+    assert_eq!(update.catalogs.len(), 2);
+    // Offers repeated in the regional catalog are retrieved once per catalog.
+    let retrieved: usize = update
+        .retrieved
+        .iter()
+        .map(|(_, retrieved)| retrieved.offers.len())
+        .sum();
+    assert_eq!(retrieved, 7);
+
+    let mut cache = OfferCache::default();
+    cache.update(&netto, None, update, Utc::now());
+    assert_eq!(
+        cache.catalog_ids(&netto, None),
+        HashSet::from(["8b1e2Qx1".to_string(), "8b1e9Rg2".to_string()])
+    );
+
+    let filter = parse_query(&["kaffe".to_string(), "price<40".to_string()]).unwrap();
+    let mut found: Vec<_> = cache
+        .offers(catalog_day())
+        .filter(|offer| filter.matches(offer))
+        .collect();
+    // The regional catalog repeats the Kaffe of the national one under another id.
+    assert_eq!(offer::dedup(&mut found), 1);
+    let found: Vec<_> = found
+        .iter()
+        .map(|offer| (offer.id.as_str(), offer.price))
+        .collect();
+    assert_eq!(found, [("f10aC7pq", 39.0)]);
+
+    // Every catalog ran out by the end of the month.
+    let later = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap();
+    assert_eq!(cache.offers(later).count(), 0);
+}